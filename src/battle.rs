@@ -0,0 +1,457 @@
+//! Monte Carlo simulation of a battle between two armies
+use rand::Rng;
+use std::fmt::Display;
+
+use crate::{
+    dice::{low_luck_hits, roll, roll_best},
+    tech::{apply_tech_effects, Combat, Tech},
+    Category, Edition, Troops, IPC,
+};
+
+/// Battles still going after this many rounds are counted as a draw
+const MAX_ROUNDS: u32 = 100;
+/// The attack of an infantry supported by artillery
+const ARTILLERY_SUPPORTED_ATTACK: u8 = 2;
+
+/// The aggregated outcome of a number of simulated battles
+#[derive(Debug, Default)]
+pub struct BattleReport {
+    iterations: u32,
+    attacker_wins: u32,
+    defender_wins: u32,
+    draws: u32,
+    /// Whether the attacker could retreat, so the retreats are worth reporting
+    retreating: bool,
+    retreats: u32,
+    attacker_survivors: u64,
+    defender_survivors: u64,
+    rounds: u64,
+    /// The value of the attackers left after each battle
+    attacker_values: Vec<IPC>,
+    /// The rounds fought in each battle
+    round_counts: Vec<u32>,
+}
+
+impl BattleReport {
+    fn percentage(&self, count: u32) -> f64 {
+        100.0 * count as f64 / self.iterations as f64
+    }
+
+    fn average(&self, total: u64) -> f64 {
+        total as f64 / self.iterations as f64
+    }
+
+    /// The spread of the outcomes rather than just their averages
+    pub fn distribution(&self) -> Distribution<'_> {
+        Distribution { report: self }
+    }
+}
+
+/// Percentiles of the surviving attackers and the rounds fought, along with how often each number of rounds came up
+pub struct Distribution<'a> {
+    report: &'a BattleReport,
+}
+
+/// The value at or below which `percent` of the sorted values lie
+fn percentile<T: Copy + Default>(sorted: &[T], percent: usize) -> T {
+    if sorted.is_empty() {
+        return T::default();
+    }
+
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+impl Display for Distribution<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut values = self.report.attacker_values.clone();
+        values.sort_unstable();
+        let mut rounds = self.report.round_counts.clone();
+        rounds.sort_unstable();
+
+        writeln!(
+            f,
+            "Surviving attacker value: P10 {}, P50 {}, P90 {}",
+            percentile(&values, 10),
+            percentile(&values, 50),
+            percentile(&values, 90)
+        )?;
+        writeln!(
+            f,
+            "Rounds fought: P10 {}, P50 {}, P90 {}",
+            percentile(&rounds, 10),
+            percentile(&rounds, 50),
+            percentile(&rounds, 90)
+        )?;
+
+        writeln!(f, "Battles by rounds fought:")?;
+        for chunk in rounds.chunk_by(|a, b| a == b) {
+            writeln!(f, "\t{:>3}: {:.1}%", chunk[0], self.report.percentage(chunk.len() as u32))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for BattleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Simulated {} battles:", self.iterations)?;
+        writeln!(f, "\tAttacker wins: {:.1}%", self.percentage(self.attacker_wins))?;
+        writeln!(f, "\tDefender wins: {:.1}%", self.percentage(self.defender_wins))?;
+        writeln!(f, "\tDraws: {:.1}%", self.percentage(self.draws))?;
+        if self.retreating {
+            writeln!(f, "\tAttacker retreats: {:.1}%", self.percentage(self.retreats))?;
+        }
+        writeln!(f, "Expected surviving attackers: {:.2}", self.average(self.attacker_survivors))?;
+        writeln!(f, "Expected surviving defenders: {:.2}", self.average(self.defender_survivors))?;
+        writeln!(f, "Average rounds fought: {:.2}", self.average(self.rounds))?;
+
+        Ok(())
+    }
+}
+
+/// The rules the battle is fought by, beyond who takes part
+pub struct Rules<'a> {
+    /// Count the hits from the combined power of each side instead of rolling for every unit
+    pub low_luck: bool,
+    /// The order both sides take their casualties in
+    pub losses: LossOrder<'a>,
+    /// The attacker retreats after a round that leaves it with this many units or fewer
+    pub retreat_at: Option<usize>,
+    /// The edition the units are priced by, whose chart decides what the technologies of both sides do
+    pub edition: Edition,
+}
+
+/// The order each side takes its casualties in
+pub enum LossOrder<'a> {
+    /// The cheapest units are lost first
+    Cost,
+    /// Like `Cost`, but infantry is kept until every other unit is lost
+    KeepInfantry,
+    /// The listed troops are lost first in the order given, then the rest by cost
+    Custom(&'a [Troops]),
+}
+
+impl LossOrder<'_> {
+    /// Units with a lower rank are lost first, ties go to the cheaper unit
+    fn rank(&self, troop: Troops) -> usize {
+        match self {
+            LossOrder::Cost => 0,
+            LossOrder::KeepInfantry => usize::from(matches!(troop, Troops::Infantry | Troops::MechInfantry)),
+            LossOrder::Custom(priority) => priority.iter().position(|other| *other == troop).unwrap_or(priority.len()),
+        }
+    }
+}
+
+/// The result of a single battle
+struct Outcome {
+    attackers: usize,
+    defenders: usize,
+    /// The value of the attackers that survived
+    attacker_value: IPC,
+    rounds: u32,
+    /// Whether the attacker retreated rather than fighting to the end
+    retreated: bool,
+}
+
+/// Runs the battle `iterations` times and collects the results
+///
+/// The attackers fight with the technologies in `attacker_techs`, the defenders with those in `defender_techs`.
+pub fn simulate(
+    attackers: &[(Troops, i32)],
+    defenders: &[(Troops, i32)],
+    attacker_techs: &[Tech],
+    defender_techs: &[Tech],
+    iterations: u32,
+    rules: &Rules,
+    rng: &mut impl Rng,
+) -> BattleReport {
+    let attackers = expand(attackers, attacker_techs, rules);
+    let defenders = expand(defenders, defender_techs, rules);
+
+    let mut report = BattleReport {
+        iterations,
+        retreating: rules.retreat_at.is_some(),
+        ..Default::default()
+    };
+
+    for _ in 0..iterations {
+        let outcome = fight(attackers.clone(), defenders.clone(), rules, rng);
+
+        match (outcome.attackers, outcome.defenders) {
+            _ if outcome.retreated => report.retreats += 1,
+            (0, 0) => report.draws += 1,
+            (_, 0) => report.attacker_wins += 1,
+            (0, _) => report.defender_wins += 1,
+            _ => report.draws += 1,
+        }
+
+        report.attacker_survivors += outcome.attackers as u64;
+        report.defender_survivors += outcome.defenders as u64;
+        report.rounds += outcome.rounds as u64;
+        report.attacker_values.push(outcome.attacker_value);
+        report.round_counts.push(outcome.rounds);
+    }
+
+    report
+}
+
+/// Turns a list of troop counts into one entry per unit, ordered so the units lost first come first
+fn expand(army: &[(Troops, i32)], techs: &[Tech], rules: &Rules) -> Vec<Combat> {
+    let mut units: Vec<Troops> = army
+        .iter()
+        .flat_map(|(troop, ammount)| std::iter::repeat_n(*troop, *ammount as usize))
+        .collect();
+
+    units.sort_by_key(|troop| (rules.losses.rank(*troop), troop.cost_in(rules.edition)));
+    units.into_iter().map(|troop| apply_tech_effects(troop, techs, rules.edition.tech_chart())).collect()
+}
+
+fn fight(mut attackers: Vec<Combat>, mut defenders: Vec<Combat>, rules: &Rules, rng: &mut impl Rng) -> Outcome {
+    let low_luck = rules.low_luck;
+    let mut rounds = 0;
+    let mut retreated = false;
+
+    anti_aircraft_fire(&mut attackers, &defenders, low_luck, rng);
+
+    while !attackers.is_empty() && !defenders.is_empty() && rounds < MAX_ROUNDS {
+        // Neither side can ever score a hit, so the battle can't be decided
+        let defenseless = |unit: &Combat| unit.defense == 0 || unit.troop == Troops::AAA;
+        if attackers.iter().all(|unit| unit.attack == 0) && defenders.iter().all(defenseless) {
+            break;
+        }
+
+        // Submarines strike before the round unless the enemy has a Destroyer, and what they sink doesn't fire back
+        let attacker_surprise = surprise_strike(&defenders);
+        let defender_surprise = surprise_strike(&attackers);
+        let strikes_first = |surprise: bool| move |unit: &Combat| surprise && unit.troop == Troops::Submarine;
+
+        let attacker_hits = hits(&attackers, true, low_luck, strikes_first(attacker_surprise), rng);
+        let defender_hits = hits(&defenders, false, low_luck, strikes_first(defender_surprise), rng);
+        take_casualties(&mut defenders, attacker_hits);
+        take_casualties(&mut attackers, defender_hits);
+
+        let attacker_hits = hits(&attackers, true, low_luck, |unit| !strikes_first(attacker_surprise)(unit), rng);
+        let defender_hits = hits(&defenders, false, low_luck, |unit| !strikes_first(defender_surprise)(unit), rng);
+        take_casualties(&mut defenders, attacker_hits);
+        take_casualties(&mut attackers, defender_hits);
+        rounds += 1;
+
+        let weakened = rules.retreat_at.is_some_and(|retreat_at| attackers.len() <= retreat_at);
+        if weakened && !attackers.is_empty() && !defenders.is_empty() {
+            retreated = true;
+            break;
+        }
+    }
+
+    Outcome {
+        attackers: attackers.len(),
+        defenders: defenders.len(),
+        attacker_value: attackers.iter().map(|unit| unit.troop.cost_in(rules.edition)).sum(),
+        rounds,
+        retreated,
+    }
+}
+
+/// Submarines get a surprise strike unless one of the enemies is a Destroyer
+fn surprise_strike(enemies: &[Combat]) -> bool {
+    !enemies.iter().any(|unit| unit.troop == Troops::Destroyer)
+}
+
+/// The hits scored by the units of `army` that `fires` picks, attacking or defending
+fn hits(army: &[Combat], attacking: bool, low_luck: bool, fires: impl Fn(&Combat) -> bool, rng: &mut impl Rng) -> usize {
+    let values: Vec<(u8, u8)> = if attacking {
+        army.iter().zip(attack_values(army)).map(|(unit, attack)| (attack, unit.attack_dice)).collect()
+    } else {
+        // AAA guns only fire at aircraft before combat, in the rounds themselves they are just casualties
+        army.iter()
+            .map(|unit| (if unit.troop == Troops::AAA { 0 } else { unit.defense }, 1))
+            .collect()
+    };
+    let firing = army.iter().zip(values).filter(|(unit, _)| fires(unit)).map(|(_, value)| value);
+
+    if low_luck {
+        low_luck_hits(firing.map(|(value, _)| value), rng)
+    } else {
+        firing.filter(|(value, dice)| roll_best(*dice, rng) <= *value).count()
+    }
+}
+
+/// Before combat every AAA gun fires one shot at an attacking air unit, each hit on a 1 is lost
+fn anti_aircraft_fire(attackers: &mut Vec<Combat>, defenders: &[Combat], low_luck: bool, rng: &mut impl Rng) {
+    let guns = defenders.iter().filter(|unit| unit.troop == Troops::AAA).count();
+    let aircraft = attackers.iter().filter(|unit| unit.troop.category() == Category::Air).count();
+
+    let shots = guns.min(aircraft);
+    if shots == 0 {
+        return;
+    }
+
+    let mut hits = if low_luck {
+        low_luck_hits(std::iter::repeat_n(1, shots), rng)
+    } else {
+        (0..shots).filter(|_| roll(rng) == 1).count()
+    };
+
+    // The aircraft are lost in the same order as in regular combat
+    attackers.retain(|unit| {
+        if hits > 0 && unit.troop.category() == Category::Air {
+            hits -= 1;
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// The attack of every unit this round, with each artillery raising the attack of one infantry to 2
+fn attack_values(army: &[Combat]) -> Vec<u8> {
+    let mut support = army.iter().filter(|unit| unit.troop == Troops::Artillery).count();
+
+    army.iter()
+        .map(|unit| {
+            if support > 0 && matches!(unit.troop, Troops::Infantry | Troops::MechInfantry) {
+                support -= 1;
+                unit.attack.max(ARTILLERY_SUPPORTED_ATTACK)
+            } else {
+                unit.attack
+            }
+        })
+        .collect()
+}
+
+fn take_casualties(army: &mut Vec<Combat>, hits: usize) {
+    army.drain(..hits.min(army.len()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn rules(low_luck: bool) -> Rules<'static> {
+        Rules {
+            low_luck,
+            losses: LossOrder::Cost,
+            retreat_at: None,
+            edition: Edition::AA1942,
+        }
+    }
+
+    fn battle(attackers: &[(Troops, i32)], defenders: &[(Troops, i32)], techs: &[Tech], rules: &Rules) -> BattleReport {
+        simulate(attackers, defenders, techs, &[], 1000, rules, &mut StdRng::seed_from_u64(7))
+    }
+
+    #[test]
+    fn heavy_bombers_roll_two_dice() {
+        let bombers = expand(&[(Troops::Bomber, 1)], &[Tech::HeavyBombers], &rules(false));
+        assert_eq!(bombers[0].attack_dice, 2);
+
+        let attackers = [(Troops::Bomber, 2)];
+        let defenders = [(Troops::Infantry, 3)];
+        let plain = battle(&attackers, &defenders, &[], &rules(false));
+        let heavy = battle(&attackers, &defenders, &[Tech::HeavyBombers], &rules(false));
+        assert!(heavy.attacker_wins > plain.attacker_wins);
+    }
+
+    #[test]
+    fn each_artillery_supports_one_infantry() {
+        let army = expand(&[(Troops::Infantry, 3), (Troops::Artillery, 2)], &[], &rules(false));
+        let mut supported: Vec<(Troops, u8)> = army.iter().map(|unit| unit.troop).zip(attack_values(&army)).collect();
+        supported.sort();
+
+        let infantry = [(Troops::Infantry, 1), (Troops::Infantry, 2), (Troops::Infantry, 2)];
+        assert_eq!(supported[..3], infantry);
+        assert_eq!(supported[3..], [(Troops::Artillery, 2), (Troops::Artillery, 2)]);
+    }
+
+    #[test]
+    fn aaa_guns_only_fire_at_aircraft() {
+        let report = battle(&[(Troops::Tank, 3)], &[(Troops::AAA, 3)], &[], &rules(false));
+        assert_eq!(report.defender_wins, 0);
+        assert_eq!(report.attacker_survivors, 3 * 1000);
+
+        // The shots at the aircraft come from the seed, so they are the same every time
+        let aircraft = [(Troops::Fighter, 4)];
+        let first = battle(&aircraft, &[(Troops::AAA, 2)], &[], &rules(false));
+        let second = battle(&aircraft, &[(Troops::AAA, 2)], &[], &rules(false));
+        assert_eq!(first.attacker_survivors, second.attacker_survivors);
+        assert!(first.attacker_survivors < 4 * 1000);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_distribution() {
+        let attackers = [(Troops::Infantry, 4), (Troops::Tank, 2)];
+        let defenders = [(Troops::Infantry, 5)];
+        let first = battle(&attackers, &defenders, &[], &rules(false));
+        let second = battle(&attackers, &defenders, &[], &rules(false));
+
+        assert_eq!(first.attacker_values, second.attacker_values);
+        assert_eq!(first.round_counts, second.round_counts);
+        assert_eq!(first.distribution().to_string(), second.distribution().to_string());
+        assert_eq!(percentile(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10], 50), 5);
+        assert_eq!(percentile::<u32>(&[], 90), 0);
+    }
+
+    #[test]
+    fn submarines_strike_first_unless_there_is_a_destroyer() {
+        let cruiser = expand(&[(Troops::Cruiser, 1)], &[], &rules(true));
+        let destroyer = expand(&[(Troops::Destroyer, 1)], &[], &rules(true));
+        assert!(surprise_strike(&cruiser));
+        assert!(!surprise_strike(&destroyer));
+
+        // With low luck the three submarines always sink one ship, the cruiser before it can fire back
+        let submarines = [(Troops::Submarine, 3)];
+        let report = battle(&submarines, &[(Troops::Cruiser, 1)], &[], &rules(true));
+        assert_eq!(report.attacker_survivors, 3 * 1000);
+
+        let report = battle(&submarines, &[(Troops::Destroyer, 1)], &[], &rules(true));
+        assert_eq!(report.attacker_wins, 1000);
+        assert!(report.attacker_survivors < 3 * 1000);
+    }
+
+    #[test]
+    fn losses_follow_the_casualty_order_and_edition() {
+        let army = [(Troops::Tank, 1), (Troops::Infantry, 2)];
+        let by_cost: Vec<Troops> = expand(&army, &[], &rules(false)).iter().map(|unit| unit.troop).collect();
+        assert_eq!(by_cost, [Troops::Infantry, Troops::Infantry, Troops::Tank]);
+
+        let keep_infantry = Rules {
+            losses: LossOrder::KeepInfantry,
+            ..rules(false)
+        };
+        let kept: Vec<Troops> = expand(&army, &[], &keep_infantry).iter().map(|unit| unit.troop).collect();
+        assert_eq!(kept, [Troops::Tank, Troops::Infantry, Troops::Infantry]);
+
+        let attackers = [(Troops::Tank, 3), (Troops::Infantry, 3)];
+        let defenders = [(Troops::Infantry, 3)];
+        let cost = battle(&attackers, &defenders, &[], &rules(false));
+        let infantry_kept = battle(&attackers, &defenders, &[], &keep_infantry);
+        assert!(infantry_kept.attacker_values.iter().sum::<IPC>() < cost.attacker_values.iter().sum::<IPC>());
+
+        // The survivors are valued at the prices of the edition played
+        let revised = Rules {
+            edition: Edition::Revised,
+            ..rules(false)
+        };
+        assert_eq!(battle(&[(Troops::Tank, 1)], &[], &[], &rules(false)).attacker_values[0], 6);
+        assert_eq!(battle(&[(Troops::Tank, 1)], &[], &[], &revised).attacker_values[0], 5);
+    }
+
+
+    #[test]
+    fn defending_jet_fighters_hit_more_often() {
+        let jets = expand(&[(Troops::Fighter, 1)], &[Tech::JetFighters], &rules(false));
+        assert_eq!(jets[0].defense, 5);
+
+        let attackers = [(Troops::Infantry, 6)];
+        let defenders = [(Troops::Fighter, 2)];
+        let defend = |techs: &[Tech]| {
+            simulate(&attackers, &defenders, &[], techs, 1000, &rules(false), &mut StdRng::seed_from_u64(7))
+        };
+        let plain = defend(&[]);
+        let jets = defend(&[Tech::JetFighters]);
+        assert!(jets.defender_wins > plain.defender_wins);
+        assert!(jets.attacker_survivors < plain.attacker_survivors);
+    }
+}
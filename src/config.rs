@@ -0,0 +1,201 @@
+//! User settings that apply to every game
+//!
+//! Settings given on the command line take precedence over the config file,
+//! which in turn takes precedence over the built in defaults.
+use log::error;
+use ron::{de::from_reader, ser::{PrettyConfig, to_writer_pretty}};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    path::PathBuf,
+};
+
+use crate::{
+    error::{AppError, Result},
+    Edition, Lang,
+};
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Config {
+    /// The edition used when none is given on the command line
+    #[serde(default)]
+    pub edition: Option<Edition>,
+    /// What the money is called when none is given on the command line
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// The language troop names are shown in when none is given on the command line
+    #[serde(default)]
+    pub lang: Option<Lang>,
+    /// Whether games are saved compactly when --compact isn't given
+    #[serde(default)]
+    pub compact: Option<bool>,
+    /// How many spaces saved games are indented by
+    #[serde(default)]
+    pub indent: Option<usize>,
+    /// Whether saved games name the type of every struct
+    #[serde(default)]
+    pub struct_names: Option<bool>,
+    /// Whether purchases of no troops are dropped when a game is loaded
+    #[serde(default)]
+    pub purge_zeros: Option<bool>,
+}
+
+impl Config {
+    /// Loads the config file, falling back to the defaults if there is none
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+
+        match OpenOptions::new().read(true).open(&path) {
+            Ok(file) => from_reader(file).unwrap_or_else(|e| {
+                error!("Failed to read config from {} due to error {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = config_path().ok_or_else(no_config_dir)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(AppError::io(format!("create config directory {}", dir.display())))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(AppError::io("save config"))?;
+
+        to_writer_pretty(file, self, PrettyConfig::default()).map_err(AppError::format("save config"))
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.edition {
+            Some(edition) => writeln!(f, "edition: {edition}")?,
+            None => writeln!(f, "edition: not set")?,
+        }
+
+        match &self.currency {
+            Some(currency) => writeln!(f, "currency: {currency}")?,
+            None => writeln!(f, "currency: not set")?,
+        }
+
+        match self.lang {
+            Some(lang) => writeln!(f, "language: {lang}")?,
+            None => writeln!(f, "language: not set")?,
+        }
+
+        match self.compact {
+            Some(compact) => writeln!(f, "compact: {compact}")?,
+            None => writeln!(f, "compact: not set")?,
+        }
+
+        match self.indent {
+            Some(indent) => writeln!(f, "indent: {indent}")?,
+            None => writeln!(f, "indent: not set")?,
+        }
+
+        match self.struct_names {
+            Some(struct_names) => writeln!(f, "struct names: {struct_names}")?,
+            None => writeln!(f, "struct names: not set")?,
+        }
+
+        match self.purge_zeros {
+            Some(purge_zeros) => writeln!(f, "purge zeros: {purge_zeros}"),
+            None => writeln!(f, "purge zeros: not set"),
+        }
+    }
+}
+
+/// The config file lives in the config directory
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.ron"))
+}
+
+/// `$XDG_CONFIG_HOME/axis_tracker`, or `~/.config/axis_tracker` if that isn't set
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("axis_tracker"))
+}
+
+/// The names of the files in a folder of the config directory, in alphabetical order
+pub fn list_named_files(folder: &str) -> Vec<String> {
+    let Some(dir) = config_dir() else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(dir.join(folder)) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .filter_map(|path| path.file_stem().map(|name| name.to_string_lossy().into_owned()))
+        .collect();
+
+    names.sort();
+    names
+}
+
+/// The file `name` is stored in within a folder of the config directory,
+/// names that would point outside the folder are refused
+pub fn named_file(folder: &str, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(AppError::Refused(format!("{name:?} can't be used as a name")));
+    }
+
+    let dir = config_dir().ok_or_else(no_config_dir)?;
+    Ok(dir.join(folder).join(format!("{name}.ron")))
+}
+
+/// The error for when neither `$XDG_CONFIG_HOME` nor `$HOME` is set
+pub fn no_config_dir() -> AppError {
+    AppError::Refused("Could not find a config directory, set $XDG_CONFIG_HOME or $HOME".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trips() {
+        let _env = crate::tests::lock_env();
+        let config = Config {
+            edition: Some(Edition::Global1940),
+            currency: Some("PU".to_string()),
+            lang: Some(Lang::De),
+            compact: Some(true),
+            indent: Some(2),
+            struct_names: None,
+            purge_zeros: Some(false),
+        };
+
+        config.save().unwrap();
+        let loaded = Config::load();
+        let _ = fs::remove_file(config_path().unwrap());
+
+        assert_eq!(loaded, config);
+    }
+
+    #[test]
+    fn missing_config_falls_back_to_the_defaults() {
+        let _env = crate::tests::lock_env();
+        let _ = fs::remove_file(config_path().unwrap());
+
+        assert_eq!(Config::load(), Config::default());
+    }
+}
@@ -0,0 +1,20 @@
+//! Rolling six sided dice
+use rand::Rng;
+
+/// Rolls a single six sided die
+pub fn roll(rng: &mut impl Rng) -> u8 {
+    rng.gen_range(1..=6)
+}
+
+/// Rolls several dice and keeps the best one, which is the lowest since low rolls hit
+pub fn roll_best(dice: u8, rng: &mut impl Rng) -> u8 {
+    (0..dice.max(1)).map(|_| roll(rng)).min().unwrap_or(6)
+}
+
+/// Low luck: every full 6 of combined power is a hit, and one die is rolled against what is left over
+pub fn low_luck_hits(values: impl Iterator<Item = u8>, rng: &mut impl Rng) -> usize {
+    let power: usize = values.map(usize::from).sum();
+    let remainder = (power % 6) as u8;
+
+    power / 6 + usize::from(remainder > 0 && roll(rng) <= remainder)
+}
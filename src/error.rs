@@ -0,0 +1,73 @@
+//! The ways a command can fail
+//!
+//! Commands return these rather than printing them, so they are reported in one place
+//! and the exit code can be chosen from them.
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+use crate::{currency, Edition, Nation, Troops, IPC};
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    /// There is no game in the file the command applies to, most likely because setup hasn't been run yet
+    #[error("No game found in {} — run `setup <ipc>` first.", .0.display())]
+    FileNotFound(PathBuf),
+    /// The game couldn't be parsed and there was no backup to recover it from
+    #[error("Failed to read the game in {source_name} at line {line}, column {column}: {message}")]
+    ParseError {
+        source_name: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("Can't spend {needed} {}, you only have {available}", currency())]
+    InsufficientIpc { needed: IPC, available: IPC },
+    #[error("{troop}s can't be bought in {edition}, list shows the troops that can")]
+    UnavailableTroop { troop: Troops, edition: Edition },
+    #[error("{0} is not part of this game, use setup --nation to add it")]
+    UnknownNation(Nation),
+    /// A sum of IPC or troops got too large to be counted
+    #[error("{0}")]
+    Overflow(String),
+    /// The user answered no when asked to confirm
+    #[error("{0}")]
+    Declined(String),
+    /// Reading or writing a file failed
+    #[error("Failed to {action} due to error {source}")]
+    Io {
+        action: String,
+        #[source]
+        source: io::Error,
+    },
+    /// Turning something into or out of JSON or RON failed
+    #[error("Failed to {action} due to error {message}")]
+    Format { action: String, message: String },
+    /// The command can't be carried out as it was given
+    #[error("{0}")]
+    Refused(String),
+}
+
+impl AppError {
+    /// For use with `map_err`, describing what was being done when the IO failed
+    pub fn io(action: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        move |source| AppError::Io {
+            action: action.into(),
+            source,
+        }
+    }
+
+    /// For use with `map_err`, describing what was being converted when it failed
+    pub fn format<E: std::fmt::Display>(action: impl Into<String>) -> impl FnOnce(E) -> Self {
+        move |e| AppError::Format {
+            action: action.into(),
+            message: e.to_string(),
+        }
+    }
+
+    /// Whether the error is a failure of the system rather than of what the user asked for
+    pub fn is_internal(&self) -> bool {
+        matches!(self, AppError::Io { .. } | AppError::Format { .. })
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AppError>;
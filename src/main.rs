@@ -1,20 +1,149 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use log::{error, debug};
-use ron::{de::from_reader, ser::{PrettyConfig, to_writer_pretty}};
+use chrono::{DateTime, Utc};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use log::{error, debug, info};
+use owo_colors::{OwoColorize, Style};
+use rand::{rngs::StdRng, SeedableRng};
+use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fmt::Display,
-    fs::OpenOptions,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock, PoisonError,
+    },
 };
 
+mod battle;
+mod config;
+mod dice;
+mod error;
+mod plan;
+mod profile;
+mod table;
+mod tech;
+
+use battle::{LossOrder, Rules};
+use config::Config;
+use error::{AppError, Result};
+use plan::Plan;
+use table::{Align, Table};
+use tech::{Tech, TechChart};
+
 const FILE: &str = "state.ron";
+/// The file name that stands for reading the game from stdin and writing it to stdout
+const STDIO: &str = "-";
+/// The version of the save format, bumped whenever a migration is needed
+const VERSION: u32 = 2;
+const OVERFLOW: &str = "The purchase total overflowed";
+const DEFAULT_CURRENCY: &str = "IPC";
+/// How many rounds the income trend in the status covers by default
+const TREND_WINDOW: usize = 3;
+/// How many rounds the income forecast follows the trend of by default
+const FORECAST_WINDOW: usize = 5;
+/// The most IPC optimize will find a purchase for, it takes time and memory in proportion to it
+const MAX_OPTIMIZED_IPC: IPC = 10_000;
+/// Starting out with more than this is most likely a typo
+const SANE_INITIAL_IPC: IPC = 500;
+/// How deep compact saves still put every value on its own line, deeper values share a line
+const COMPACT_DEPTH: usize = 3;
+/// The indentation of saved games, the same as RON uses by default
+const DEFAULT_INDENT: usize = 4;
 
+#[allow(clippy::upper_case_acronyms)]
 type IPC = i32;
 
+/// Set by --quiet to silence the messages that only report what a command did
+static QUIET: AtomicBool = AtomicBool::new(false);
+static CURRENCY: OnceLock<String> = OnceLock::new();
+static LANG: OnceLock<Lang> = OnceLock::new();
+/// The game read from stdin, kept since stdin can only be read once and updated whenever it is saved
+static STDIO_GAME: Mutex<Option<String>> = Mutex::new(None);
+/// The games saved by the commands of a batch, only written to their files once every command succeeded
+static BATCH_GAMES: Mutex<Option<BTreeMap<PathBuf, String>>> = Mutex::new(None);
+static SAVE_STYLE: OnceLock<SaveStyle> = OnceLock::new();
+/// Set by the config to drop purchases of no troops whenever a game is loaded
+static PURGE_ON_LOAD: AtomicBool = AtomicBool::new(false);
+
+/// The language troop names are shown in, set with --lang or in the config file
+fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+/// What the money is called in the output, set with --currency or in the config file
+fn currency() -> &'static str {
+    CURRENCY.get().map_or(DEFAULT_CURRENCY, String::as_str)
+}
+
+/// Prints like `println!` unless --quiet was given, for messages that are neither results nor problems
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Exits with 0 if the command succeeded, 1 if it failed and 2 if the arguments were invalid"
+)]
 struct AppArgs {
+    #[command(flatten)]
+    options: Options,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+// The options that apply to every command
+#[derive(Args)]
+struct Options {
+    /// The file the game state is stored in, defaults to the active game or state.ron.
+    /// With - the game is read from stdin and written to stdout, which also implies --quiet
+    #[arg(long, env = "AXIS_STATE_FILE", global = true)]
+    file: Option<PathBuf>,
+    /// Refuse purchases that exceed the available IPC instead of just warning
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Never color the output, this is also the case if NO_COLOR is set
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// The nation whose economy the command applies to
+    #[arg(long, value_enum, default_value_t, global = true)]
+    nation: Nation,
+    /// Only print results, warnings and errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Log more details, repeat for even more. Overrides RUST_LOG
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    /// What to call the money in the output, such as PU. Defaults to the config file or IPC
+    #[arg(long, value_name = "LABEL", global = true)]
+    currency: Option<String>,
+    /// Save the game with fewer lines, which is easier to diff
+    // Not global, status has a --compact of its own
+    #[arg(long)]
+    compact: bool,
+    /// The language troop names are shown in, defaults to the config file or English
+    #[arg(long, value_enum, global = true)]
+    lang: Option<Lang>,
+    /// Show the effect of the command without saving the changes it makes, refusing commands that only change files
+    #[arg(long, global = true)]
+    no_persist: bool,
+}
+
+// A single line entered in an interactive session or read from a batch file
+#[derive(Parser)]
+#[command(no_binary_name = true, after_help = "Enter quit or exit to end the session")]
+struct Prompt {
     #[command(subcommand)]
     command: Commands,
 }
@@ -24,243 +153,4560 @@ enum Commands {
     /// Setup a new game
     Setup {
         /// The IPC you start out with
-        initial_icp: IPC,
+        #[arg(allow_negative_numbers = true)]
+        initial_ipc: IPC,
+        /// The edition of the game being played, defaults to the configured edition
+        #[arg(long)]
+        edition: Option<Edition>,
+        /// Overwrite the nation's game if it is already in progress
+        #[arg(long)]
+        force: bool,
     },
     /// Show the current status of the game
-    Status,
+    Status {
+        /// Show the status of every nation in the game
+        #[arg(long)]
+        all: bool,
+        /// The format to print the status in
+        #[arg(long, value_enum, default_value_t)]
+        format: StatusFormat,
+        /// Print the status as JSON, the same as --format json
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+        /// Print the status on a single line, the same as --format compact
+        #[arg(long, conflicts_with_all = ["format", "json"])]
+        compact: bool,
+        /// List the purchases one per line instead of as a table
+        #[arg(long)]
+        plain: bool,
+        /// Also list the purchases and removals made this round
+        #[arg(long)]
+        actions: bool,
+        /// The order to list the purchases in
+        #[arg(long, value_enum, default_value_t)]
+        sort: PurchaseOrder,
+        /// How many of the last rounds to show the income of
+        #[arg(long, default_value_t = TREND_WINDOW, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        window: usize,
+    },
     /// Add a troop type to current purchase
     Purchase {
         /// The troop type to add to purchase
-        troop: Troops,
+        #[arg(required_unless_present = "buy", conflicts_with = "buy", value_parser = TroopParser)]
+        troop: Option<Troops>,
         /// The ammount to add
-        #[arg(default_value_t = 1)]
+        #[arg(default_value_t = 1, allow_negative_numbers = true)]
         ammount: i32,
+        /// Add several troop types at once, e.g. --buy Infantry:3 --buy Tank:2
+        #[arg(long, value_parser = parse_troop_count)]
+        buy: Vec<(Troops, i32)>,
+        /// Limit how many of the troop type can be bought each round, this applies to later purchases too
+        #[arg(long, requires = "troop", value_parser = clap::value_parser!(i32).range(0..))]
+        max: Option<i32>,
+        /// Show the result of the purchase without saving it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show what buying more troops on top of the current purchase would cost, without saving anything
+    Whatif {
+        /// The troops to buy, e.g. --buy Tank:3 --buy Fighter:2
+        #[arg(long, required = true, value_parser = parse_troop_count)]
+        buy: Vec<(Troops, i32)>,
     },
     /// Remove something from the purchase this round
     Remove {
         /// The troop type to remove from purchase
+        #[arg(value_parser = TroopParser)]
         troop: Troops,
-        /// The ammount to remove
+        /// The ammount to remove, defaults to one
+        #[arg(value_parser = clap::value_parser!(i32).range(1..), conflicts_with = "all")]
         ammount: Option<i32>,
+        /// Remove every troop of this type from the purchase
+        #[arg(long)]
+        all: bool,
+        /// Show the result of the removal without saving it
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Checks and Commits the purchase and updates to the new ipc
     Commit {
-        /// The ipc you get this round
-        ipc: IPC
+        /// The ipc you get this round, defaults to the income set with set-income
+        ipc: Option<IPC>,
+        /// Change the income set with set-income by this much, such as +2 or -3, and get it this round
+        #[arg(long, conflicts_with_all = ["ipc", "source"], allow_negative_numbers = true)]
+        delta: Option<IPC>,
+        /// Where the income comes from, e.g. --source territory:28 --source convoy:3, these must add up to the ipc
+        #[arg(long, value_name = "NAME:IPC", value_parser = parse_income_source)]
+        source: Vec<IncomeSource>,
+        /// Extra ipc gained from national objectives this round
+        #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(i32).range(0..))]
+        bonus: IPC,
+        /// Commit even though nothing was purchased this round
+        #[arg(long)]
+        skip_build: bool,
+        /// Don't ask for confirmation, which is required when stdin isn't a terminal
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Set the ipc you get each round, which commit uses unless it is given another income
+    SetIncome {
+        #[arg(value_parser = clap::value_parser!(i32).range(0..))]
+        ipc: IPC,
+    },
+    /// Spend ipc on something other than troops, such as research
+    Spend {
+        /// The ipc to spend
+        #[arg(value_parser = clap::value_parser!(i32).range(1..))]
+        ipc: IPC,
+        /// What the ipc was spent on
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Spend ipc on repairing a damaged Battleship, Aircraft Carrier or Industrial Complex
+    Repair {
+        /// The troop type repaired
+        #[arg(value_parser = TroopParser)]
+        troop: Troops,
+        /// The ipc the repairs cost
+        #[arg(value_parser = clap::value_parser!(i32).range(1..))]
+        ipc: IPC,
+    },
+    /// Put ipc aside so it can't be spent on purchases
+    Bank {
+        /// The ipc to put aside
+        #[arg(value_parser = clap::value_parser!(i32).range(1..))]
+        ipc: IPC,
+    },
+    /// Take banked ipc back so it can be spent again
+    Unbank {
+        /// The ipc to take back
+        #[arg(value_parser = clap::value_parser!(i32).range(1..))]
+        ipc: IPC,
+    },
+    /// Buy research tokens and roll for new technologies
+    Tech {
+        /// The number of tokens to buy
+        #[arg(long, default_value_t = 0)]
+        tokens: u32,
+        /// Roll every token held for a breakthrough
+        #[arg(long)]
+        roll: bool,
+        /// Seed for the dice, for reproducible results
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Discard all purchases planned this round without committing them
+    Reset {
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Switch the game to another edition, which changes the cost of the troops
+    Convert {
+        /// The edition to switch to
+        #[arg(long)]
+        to: Edition,
+        /// Show how the cost of the pending purchases changes and whether they still fit the budget
+        #[arg(long)]
+        reprice: bool,
+    },
+    /// Record the units on the board, shows them and their total value if no changes are given
+    #[command(visible_alias = "fleet")]
+    Army {
+        /// Units gained outside of purchases, e.g. --add Infantry:3 --add Tank:2
+        #[arg(long, value_parser = parse_troop_count)]
+        add: Vec<(Troops, i32)>,
+        /// Units lost in battle, e.g. --remove Fighter:1
+        #[arg(long, value_parser = parse_troop_count)]
+        remove: Vec<(Troops, i32)>,
+    },
+    /// Take units lost in combat off the board
+    Lose {
+        /// The troop type that was lost
+        #[arg(value_parser = TroopParser)]
+        troop: Troops,
+        /// The ammount lost, defaults to one
+        #[arg(default_value_t = 1, value_parser = clap::value_parser!(i32).range(1..))]
+        ammount: i32,
+        /// Lose all of the units if there are fewer on the board than were lost
+        #[arg(long)]
+        clamp: bool,
+    },
+    /// Limit how many units of a category can be committed each round, shows the limits if none are given
+    Caps {
+        /// The most land units bought each round
+        #[arg(long, value_name = "N")]
+        max_land: Option<i32>,
+        /// The most air units bought each round
+        #[arg(long, value_name = "N")]
+        max_air: Option<i32>,
+        /// The most sea units bought each round
+        #[arg(long, value_name = "N")]
+        max_sea: Option<i32>,
+        /// Remove all category limits
+        #[arg(long, conflicts_with_all = ["max_land", "max_air", "max_sea"])]
+        clear: bool,
+    },
+    /// Remove the purchases of no or a negative number of troops, which hand edits and imports can leave behind
+    PurgeZeros,
+    /// Revert the last change made to the game
+    Undo,
+    /// Show the purchases of all previous rounds
+    History {
+        /// Only show the rounds from this one on
+        #[arg(long, value_name = "ROUND", conflicts_with = "last")]
+        since: Option<u32>,
+        /// Only show the most recent rounds
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+    },
+    /// Estimate the ipc you will have next round from the trend of the recent incomes
+    Forecast {
+        /// How many of the last rounds the trend is taken over
+        #[arg(long, value_name = "N", default_value_t = FORECAST_WINDOW, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..))]
+        rounds: usize,
+    },
+    /// Show the income and spending of every round so far
+    Summary {
+        /// Also draw a bar chart of the income each round
+        #[arg(long)]
+        chart: bool,
+    },
+    /// Show the cost and combat values of a troop type
+    Info {
+        /// The troop type to show
+        #[arg(value_parser = TroopParser)]
+        troop: Troops,
+        /// The edition to show the cost in, defaults to the configured edition
+        #[arg(long)]
+        edition: Option<Edition>,
+    },
+    /// Print just the cost of one or more troop types
+    Cost {
+        /// The troop types to look up
+        #[arg(required = true, value_parser = TroopParser)]
+        troops: Vec<Troops>,
+        /// Print the cost of this many of each troop instead
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        total: i32,
+        /// The edition to show the costs in, defaults to the configured edition
+        #[arg(long)]
+        edition: Option<Edition>,
+    },
+    /// List all troop types and their costs
+    List {
+        /// Only list troops of this category
+        #[arg(long)]
+        category: Option<Category>,
+        /// The edition to show the costs in, defaults to the configured edition
+        #[arg(long)]
+        edition: Option<Edition>,
+        /// Also show the names each troop type can be given as
+        #[arg(long)]
+        aliases: bool,
+        /// The order to list the troops in
+        #[arg(long, value_enum, default_value_t)]
+        sort: ListOrder,
+    },
+    /// Add as many of a troop type as the remaining ipc and the caps allow
+    BuyMax {
+        #[arg(value_parser = TroopParser)]
+        troop: Troops,
+    },
+    /// Show how many of each troop type you can still afford this round
+    Afford {
+        /// Only show troops of this category
+        #[arg(long)]
+        category: Option<Category>,
+        /// Print how many of each troop type can be afforded as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Rank this round's purchases by how much of the spending went to them
+    Top {
+        /// Only show the K purchases that cost the most
+        #[arg(long = "n", value_name = "K", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        limit: Option<usize>,
+    },
+    /// Suggest troops to buy with the remaining ipc so as little as possible is left over
+    Optimize {
+        /// Only suggest troops of this category
+        #[arg(long)]
+        category: Option<Category>,
+    },
+    /// Print the state of every nation in the given format
+    Export {
+        /// The format to export the game in
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Write the export to this file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Replace the state of every nation with one read from a file
+    Import {
+        /// The file to import the game from
+        path: PathBuf,
+        /// The format of the imported file
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    /// Simulate a battle and show the odds of winning it
+    Battle {
+        /// The attacking units, e.g. Infantry:3,Tank:2
+        #[arg(long, required = true, value_delimiter = ',', value_parser = parse_troop_count)]
+        attackers: Vec<(Troops, i32)>,
+        /// The defending units, e.g. Infantry:4
+        #[arg(long, required = true, value_delimiter = ',', value_parser = parse_troop_count)]
+        defenders: Vec<(Troops, i32)>,
+        /// The number of battles to simulate
+        #[arg(long, default_value_t = 10000)]
+        iterations: u32,
+        /// Let the attackers use the technologies researched by the nation
+        #[arg(long)]
+        techs: bool,
+        /// The technologies the defenders have researched, e.g. jet-fighters,radar
+        #[arg(long, value_enum, value_delimiter = ',')]
+        defender_techs: Vec<Tech>,
+        /// Seed for the dice, for reproducible results
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Also show percentiles of the surviving attackers' value and of the rounds fought
+        #[arg(long)]
+        report: bool,
+        /// Count a hit for every 6 of combined power and roll one die for the rest, instead of a die per unit
+        #[arg(long)]
+        low_luck: bool,
+        /// The order both sides lose their units in
+        #[arg(long, value_enum, default_value_t)]
+        casualty_order: CasualtyOrder,
+        /// The troops lost first with --casualty-order custom, e.g. Infantry,Artillery,Tank
+        #[arg(long, value_delimiter = ',', value_parser = TroopParser, required_if_eq("casualty_order", "custom"))]
+        priority: Vec<Troops>,
+        /// The attacker retreats after a round that leaves it with this many units or fewer
+        #[arg(long, value_name = "N")]
+        retreat_at: Option<usize>,
+    },
+    /// Roll a number of dice and count the hits
+    Roll {
+        /// The number of dice to roll
+        dice: u32,
+        /// A die hits if it rolls this value or lower
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=6))]
+        hit_on: u8,
+        /// Seed for the dice, for reproducible results
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Compare the economies of the Axis and the Allies
+    Totals {
+        /// Count these nations as part of the Axis
+        #[arg(long, value_delimiter = ',')]
+        axis: Vec<Nation>,
+        /// Count these nations as part of the Allies
+        #[arg(long, value_delimiter = ',')]
+        allies: Vec<Nation>,
+    },
+    /// Show or change the settings shared by all games
+    ///
+    /// Options given on the command line always take precedence over the config,
+    /// which takes precedence over the built in defaults.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Check the game file for states the tracker would never save itself
+    Validate,
+    /// Compare the state of the nation in two game files
+    Diff {
+        /// The earlier of the two games
+        file_a: PathBuf,
+        /// The later of the two games
+        file_b: PathBuf,
+        /// Print the differences as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Keep several named games and switch between them
+    Game {
+        #[command(subcommand)]
+        action: GameAction,
+    },
+    /// Save purchases under a name and reuse them in later rounds
+    Plan {
+        #[command(subcommand)]
+        action: PlanAction,
+    },
+    /// Enter commands at a prompt one after another, until the session is ended
+    Interactive,
+    /// Run the commands in a file one line at a time, stopping at the first that fails
+    ///
+    /// Blank lines and lines starting with # are skipped.
+    Batch {
+        /// The file to read the commands from
+        path: PathBuf,
+    },
+    /// Print a tab-completion script for a shell
+    Completions {
+        shell: clap_complete::Shell,
+    },
+}
+
+impl Commands {
+    /// Whether the command should only show what it would change
+    fn is_dry_run(&self) -> bool {
+        matches!(self, Commands::Purchase { dry_run: true, .. } | Commands::Remove { dry_run: true, .. })
+    }
+
+    /// The name of the command if it writes to files itself rather than returning the state to be saved
+    fn writes_directly(&self) -> Option<&'static str> {
+        match self {
+            Commands::Import { .. } => Some("import"),
+            Commands::Undo => Some("undo"),
+            Commands::Plan { action: PlanAction::Save { .. } } => Some("plan save"),
+            Commands::Game { action: GameAction::New { .. } } => Some("game new"),
+            Commands::Game { action: GameAction::Switch { .. } } => Some("game switch"),
+            Commands::Game { action: GameAction::Delete { .. } } => Some("game delete"),
+            Commands::Config { action: ConfigAction::Set { .. } } => Some("config set"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Show the current settings
+    Show,
+    /// Change a setting
+    Set {
+        #[command(subcommand)]
+        setting: Setting,
+    },
+}
+
+#[derive(Subcommand)]
+enum GameAction {
+    /// Start a new empty game and switch to it
+    New {
+        name: String,
+    },
+    /// Make the commands apply to another game
+    Switch {
+        name: String,
+    },
+    /// List the games, marking the active one
+    List,
+    /// Delete a game for good
+    Delete {
+        name: String,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PlanAction {
+    /// Save the purchases made this round as a plan
+    Save {
+        /// The name to save the plan under
+        name: String,
+    },
+    /// Add the purchases of a plan to this round
+    Load {
+        /// The name of the plan
+        name: String,
+    },
+    /// List the saved plans
+    List,
+}
+
+#[derive(Subcommand)]
+enum Setting {
+    /// The edition used when none is given
+    Edition {
+        edition: Edition,
+    },
+    /// What the money is called when --currency isn't given
+    Currency {
+        label: String,
+    },
+    /// The language troop names are shown in when --lang isn't given
+    Lang {
+        #[arg(value_enum)]
+        lang: Lang,
+    },
+    /// Whether games are saved as if --compact was given
+    Compact {
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    /// How many spaces saved games are indented by
+    Indent {
+        width: usize,
+    },
+    /// Whether saved games name the type of every struct
+    StructNames {
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    /// Whether purchases of no troops are dropped whenever a game is loaded, as purge-zeros does
+    PurgeZeros {
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+}
+
+/// Parses a `troop:amount` pair such as `Tank:2`
+fn parse_troop_count(value: &str) -> std::result::Result<(Troops, i32), String> {
+    let (troop, ammount) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected troop:amount but got `{value}`"))?;
+
+    let troop = resolve_troop(troop.trim())?;
+    let ammount = ammount
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{ammount}` is not a valid amount"))?;
+
+    if ammount <= 0 {
+        return Err(format!("the amount in `{value}` must be positive"));
     }
+
+    Ok((troop, ammount))
+}
+
+/// Finds the troop type called `name`, which may also be the start of a single troop's name or alias
+fn resolve_troop(name: &str) -> std::result::Result<Troops, String> {
+    if let Ok(troop) = Troops::from_str(name, true) {
+        return Ok(troop);
+    }
+
+    let prefix = name.to_lowercase();
+    let candidates: Vec<Troops> = Troops::value_variants()
+        .iter()
+        .copied()
+        .filter(|troop| {
+            troop.to_possible_value().is_some_and(|value| {
+                value.get_name_and_aliases().any(|alias| alias.to_lowercase().starts_with(&prefix))
+            })
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [troop] => Ok(*troop),
+        [] => Err(format!("unknown troop `{name}`, list shows the troops and the names they accept")),
+        _ => {
+            let names: Vec<String> = candidates.iter().map(Troops::to_string).collect();
+            Err(format!("`{name}` could be any of {}, give more of the name", names.join(", ")))
+        }
+    }
+}
+
+/// Parses troop arguments with `resolve_troop`, while still listing the troop types in the help
+#[derive(Clone)]
+struct TroopParser;
+
+impl clap::builder::TypedValueParser for TroopParser {
+    type Value = Troops;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        _arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> std::result::Result<Troops, clap::Error> {
+        let name = value.to_str().ok_or_else(|| clap::Error::new(clap::error::ErrorKind::InvalidUtf8).with_cmd(cmd))?;
+
+        resolve_troop(name).map_err(|message| clap::Error::raw(clap::error::ErrorKind::InvalidValue, message + "\n").with_cmd(cmd))
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = clap::builder::PossibleValue> + '_>> {
+        Some(Box::new(Troops::value_variants().iter().filter_map(Troops::to_possible_value)))
+    }
+}
+
+fn parse_income_source(value: &str) -> std::result::Result<IncomeSource, String> {
+    let (name, ipc) = value
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected name:ipc but got `{value}`"))?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("the source in `{value}` needs a name"));
+    }
+    let ipc = ipc
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{ipc}` is not a valid amount"))?;
+
+    if ipc < 0 {
+        return Err(format!("the income in `{value}` can't be negative"));
+    }
+
+    Ok(IncomeSource { name: name.to_string(), ipc })
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum PurchaseOrder {
+    /// The order troops are listed in by list
+    #[default]
+    Troop,
+    /// The most expensive troops first
+    Cost,
+    /// Alphabetically by the name of the troop
+    Name,
+    /// The troops bought the most of first
+    Amount,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum CasualtyOrder {
+    /// The cheapest units are lost first
+    #[default]
+    Cost,
+    /// The cheapest units are lost first, but infantry is kept to the last
+    KeepInfantry,
+    /// The troops given with --priority are lost first, in that order
+    Custom,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum ListOrder {
+    /// Group the troops by category
+    #[default]
+    Category,
+    /// Rank the troops by cost per attack, then by cost per defense for troops that can't attack
+    Efficiency,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum StatusFormat {
+    #[default]
+    Text,
+    Json,
+    Markdown,
+    /// A single line, such as for a terminal title or status bar
+    Compact,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum Format {
+    Json,
+    Ron,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    Json,
+    Ron,
+    /// The round history of every nation, one row per round
+    Csv,
 }
 
-#[derive(ValueEnum, Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(ValueEnum, Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum Troops {
     // Army
-    Infantery,
+    // Older versions misspelled this as Infantery, keep accepting that
+    #[value(alias = "infantery", alias = "inf", alias = "i", alias = "infanterie")]
+    #[serde(alias = "Infantery")]
+    Infantry,
+    #[value(alias = "mech", alias = "mi", alias = "mechanisierte-infanterie")]
+    MechInfantry,
+    #[value(alias = "tk", alias = "t", alias = "panzer")]
     Tank,
+    #[value(alias = "art", alias = "artillerie")]
     Artillery,
+    #[value(alias = "aa", alias = "flugabwehr", alias = "flak")]
     AAA,
+    #[value(alias = "factory", alias = "industriekomplex")]
     IC,
     // Airforce
+    #[value(alias = "ftr", alias = "f", alias = "jäger", alias = "jaeger")]
     Fighter,
+    #[value(alias = "tac", alias = "taktischer-bomber")]
+    TacticalBomber,
+    #[value(alias = "bmb", alias = "b")]
     Bomber,
     // Navy
+    #[value(alias = "bb", alias = "schlachtschiff")]
     Battleship,
+    #[value(alias = "carrier", alias = "ac", alias = "flugzeugträger", alias = "flugzeugtraeger")]
     AircraftCarrier,
+    #[value(alias = "dd", alias = "zerstörer", alias = "zerstoerer")]
     Destroyer,
+    #[value(alias = "ca", alias = "kreuzer")]
     Cruiser,
+    #[value(alias = "sub", alias = "ss", alias = "u-boot", alias = "uboot")]
     Submarine,
+    #[value(alias = "tp", alias = "trn", alias = "transporter")]
     Transport,
 }
 
+/// Everything about a troop type that doesn't depend on the game
+struct TroopInfo {
+    troop: Troops,
+    name: &'static str,
+    german_name: &'static str,
+    category: Category,
+    /// The cost in the 1942 Second Edition, other editions only list where they differ in `Troops::cost_in`
+    cost: IPC,
+    attack: u8,
+    defense: u8,
+    movement: u8,
+}
+
+/// One entry per troop type, in the same order as the enum
+const TROOPS: [TroopInfo; 15] = [
+    TroopInfo { troop: Troops::Infantry, name: "Infantry", german_name: "Infanterie", category: Category::Land, cost: 3, attack: 1, defense: 2, movement: 1 },
+    TroopInfo { troop: Troops::MechInfantry, name: "Mechanized Infantry", german_name: "Mechanisierte Infanterie", category: Category::Land, cost: 4, attack: 1, defense: 2, movement: 2 },
+    TroopInfo { troop: Troops::Tank, name: "Tank", german_name: "Panzer", category: Category::Land, cost: 6, attack: 3, defense: 3, movement: 2 },
+    TroopInfo { troop: Troops::Artillery, name: "Artillery", german_name: "Artillerie", category: Category::Land, cost: 4, attack: 2, defense: 2, movement: 1 },
+    TroopInfo { troop: Troops::AAA, name: "AAA", german_name: "Flugabwehr", category: Category::Land, cost: 5, attack: 0, defense: 1, movement: 1 },
+    TroopInfo { troop: Troops::IC, name: "IC", german_name: "Industriekomplex", category: Category::Land, cost: 15, attack: 0, defense: 0, movement: 0 },
+    TroopInfo { troop: Troops::Fighter, name: "Fighter", german_name: "Jäger", category: Category::Air, cost: 10, attack: 3, defense: 4, movement: 4 },
+    TroopInfo { troop: Troops::TacticalBomber, name: "Tactical Bomber", german_name: "Taktischer Bomber", category: Category::Air, cost: 11, attack: 3, defense: 3, movement: 4 },
+    TroopInfo { troop: Troops::Bomber, name: "Bomber", german_name: "Bomber", category: Category::Air, cost: 12, attack: 4, defense: 1, movement: 6 },
+    TroopInfo { troop: Troops::Battleship, name: "Battleship", german_name: "Schlachtschiff", category: Category::Sea, cost: 20, attack: 4, defense: 4, movement: 2 },
+    TroopInfo { troop: Troops::AircraftCarrier, name: "Aircraft Carrier", german_name: "Flugzeugträger", category: Category::Sea, cost: 14, attack: 0, defense: 2, movement: 2 },
+    TroopInfo { troop: Troops::Destroyer, name: "Destroyer", german_name: "Zerstörer", category: Category::Sea, cost: 8, attack: 2, defense: 2, movement: 2 },
+    TroopInfo { troop: Troops::Cruiser, name: "Cruiser", german_name: "Kreuzer", category: Category::Sea, cost: 12, attack: 3, defense: 3, movement: 2 },
+    TroopInfo { troop: Troops::Submarine, name: "Submarine", german_name: "U-Boot", category: Category::Sea, cost: 6, attack: 2, defense: 1, movement: 2 },
+    TroopInfo { troop: Troops::Transport, name: "Transport", german_name: "Transporter", category: Category::Sea, cost: 7, attack: 0, defense: 0, movement: 2 },
+];
+
+// Every troop type must have exactly one entry, at the index of its discriminant
+const _: () = {
+    assert!(TROOPS.len() == Troops::Transport as usize + 1, "TROOPS is missing a troop type");
+
+    let mut i = 0;
+    while i < TROOPS.len() {
+        assert!(TROOPS[i].troop as usize == i, "TROOPS is out of order with the enum");
+        i += 1;
+    }
+};
+
 impl Troops {
-    const fn get_cost(&self) -> IPC {
+    const fn info(&self) -> &'static TroopInfo {
+        &TROOPS[*self as usize]
+    }
+
+    const fn cost_in(&self, edition: Edition) -> IPC {
+        match (self, edition) {
+            (Troops::Tank, Edition::Revised) => 5,
+            // Global 1940 only lets you build minor industrial complexes
+            (Troops::IC, Edition::Global1940) => 12,
+            (Troops::Bomber, Edition::Revised) => 15,
+            (Troops::Battleship, Edition::Revised) => 24,
+            (Troops::AircraftCarrier, Edition::Revised | Edition::Global1940) => 16,
+            (Troops::Destroyer, Edition::Revised) => 12,
+            (Troops::Submarine, Edition::Revised) => 8,
+            (Troops::Transport, Edition::Revised) => 8,
+            _ => self.info().cost,
+        }
+    }
+
+    /// Whether the troop can be bought in the edition
+    const fn available_in(&self, edition: Edition) -> bool {
         match self {
-            Troops::Infantery => 3,
-            Troops::Tank => 6,
-            Troops::Artillery => 4,
-            Troops::AAA => 5,
-            Troops::IC => 15,
-            Troops::Fighter => 10,
-            Troops::Bomber => 12,
-            Troops::Battleship => 20,
-            Troops::AircraftCarrier => 14,
-            Troops::Cruiser => 12,
-            Troops::Destroyer => 8,
-            Troops::Submarine => 6,
-            Troops::Transport => 7,
+            Troops::MechInfantry | Troops::TacticalBomber => matches!(edition, Edition::Global1940),
+            _ => true,
+        }
+    }
+
+    /// Whether the troop can be damaged and then repaired for IPC
+    const fn repairable(&self) -> bool {
+        matches!(self, Troops::Battleship | Troops::AircraftCarrier | Troops::IC)
+    }
+
+    const fn name_in(&self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::En => self.info().name,
+            Lang::De => self.info().german_name,
         }
     }
+
+    const fn category(&self) -> Category {
+        self.info().category
+    }
+
+    const fn attack(&self) -> u8 {
+        self.info().attack
+    }
+
+    const fn defense(&self) -> u8 {
+        self.info().defense
+    }
+
+    const fn movement(&self) -> u8 {
+        self.info().movement
+    }
+
+    /// The ipc paid for each point of attack, `None` for troops that can't attack
+    fn cost_per_attack_in(&self, edition: Edition) -> Option<f64> {
+        cost_per_point(self.cost_in(edition), self.attack())
+    }
+
+    /// The ipc paid for each point of defense, `None` for troops that can't defend
+    fn cost_per_defense_in(&self, edition: Edition) -> Option<f64> {
+        cost_per_point(self.cost_in(edition), self.defense())
+    }
 }
 
+fn cost_per_point(cost: IPC, points: u8) -> Option<f64> {
+    (points > 0).then(|| cost as f64 / points as f64)
+}
+
+fn format_efficiency(cost: Option<f64>) -> String {
+    let currency = currency();
+    match cost {
+        Some(cost) => format!("{cost:.2} {currency}"),
+        None => "n/a".to_string(),
+    }
+}
+
+/// The unit costs from the 1942 Second Edition rulebook
+const AA1942_COSTS: [(Troops, IPC); 13] = [
+    (Troops::Infantry, 3),
+    (Troops::Tank, 6),
+    (Troops::Artillery, 4),
+    (Troops::AAA, 5),
+    (Troops::IC, 15),
+    (Troops::Fighter, 10),
+    (Troops::Bomber, 12),
+    (Troops::Battleship, 20),
+    (Troops::AircraftCarrier, 14),
+    (Troops::Destroyer, 8),
+    (Troops::Cruiser, 12),
+    (Troops::Submarine, 6),
+    (Troops::Transport, 7),
+];
+
+// Pin the prices at compile time so reordering the cost table can't silently change them
+const _: () = {
+    let mut i = 0;
+    while i < AA1942_COSTS.len() {
+        let (troop, cost) = AA1942_COSTS[i];
+        assert!(troop.cost_in(Edition::AA1942) == cost, "1942 Second Edition cost mismatch");
+        i += 1;
+    }
+};
+
 impl Display for Troops {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Troops::Infantery => write!(f, "Infantery"),
-            Troops::Tank => write!(f, "Tank"),
-            Troops::Artillery => write!(f, "Artillery"),
-            Troops::AAA => write!(f, "AAA"),
-            Troops::IC => write!(f, "IC"),
-            Troops::Fighter => write!(f, "Fighter"),
-            Troops::Bomber => write!(f, "Bomber"),
-            Troops::Battleship => write!(f, "Battleship"),
-            Troops::AircraftCarrier => write!(f, "Aircraft Carrier"),
-            Troops::Destroyer => write!(f, "Destroyer"),
-            Troops::Cruiser => write!(f, "Cruiser"),
-            Troops::Submarine => write!(f, "Submarine"),
-            Troops::Transport => write!(f, "Transport"),
-        }
+        write!(f, "{}", self.name_in(lang()))
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GameState {
-    ipc: IPC,
-    purchases: HashMap<Troops, i32>,
+/// The language troop names are shown in, everything else is always English
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+enum Lang {
+    #[default]
+    En,
+    De,
 }
 
-impl GameState {
-    fn new(ipc: IPC) -> Self {
-        Self {
-            ipc,
-            purchases: HashMap::default(),
+impl Display for Lang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lang::En => write!(f, "English"),
+            Lang::De => write!(f, "German"),
         }
     }
+}
 
-    fn get_total_cost(&self) -> IPC {
-        self.purchases.iter()
-            .fold(0, |acc, (troop, ammount)| acc + troop.get_cost() * ammount)
-    }
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+enum Edition {
+    Revised,
+    #[default]
+    AA1942,
+    Global1940,
 }
 
-impl Display for GameState {
+impl Display for Edition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Current game state:")?;
-        writeln!(f, "Purchases:")?;
-
-        let mut cost = 0;
+        match self {
+            Edition::Revised => write!(f, "Revised"),
+            Edition::AA1942 => write!(f, "1942 Second Edition"),
+            Edition::Global1940 => write!(f, "Global 1940"),
+        }
+    }
+}
 
-        for (troop, ammount) in self.purchases.iter() {
-            cost += troop.get_cost() * ammount;
-            writeln!(f, "\t{} : {} á {} ipc", troop, ammount, troop.get_cost())?;
+impl Edition {
+    /// The research chart technologies are rolled on and take effect by
+    const fn tech_chart(&self) -> TechChart {
+        match self {
+            Edition::Revised | Edition::AA1942 => TechChart::SecondEdition,
+            Edition::Global1940 => TechChart::Global1940,
         }
+    }
+}
 
-        writeln!(f, "At a total cost of {cost} ipc")?;
-        writeln!(f, "Remaining IPC: {}", self.ipc - cost)?;
+#[allow(clippy::upper_case_acronyms)]
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Nation {
+    #[default]
+    Germany,
+    Japan,
+    USA,
+    UK,
+    USSR,
+    Italy,
+    China,
+    ANZAC,
+    France,
+}
 
-        Ok(())
+impl Nation {
+    /// The side the nation fights on in the standard scenarios
+    const fn alliance(&self) -> Alliance {
+        match self {
+            Nation::Germany | Nation::Japan | Nation::Italy => Alliance::Axis,
+            Nation::USA | Nation::UK | Nation::USSR | Nation::China | Nation::ANZAC | Nation::France => Alliance::Allies,
+        }
     }
 }
 
-fn main() {
-    env_logger::Builder::default().build();
-    debug!("axsis_and_allies_trecker");
+impl Display for Nation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Nation::Germany => write!(f, "Germany"),
+            Nation::Japan => write!(f, "Japan"),
+            Nation::USA => write!(f, "USA"),
+            Nation::UK => write!(f, "UK"),
+            Nation::USSR => write!(f, "USSR"),
+            Nation::Italy => write!(f, "Italy"),
+            Nation::China => write!(f, "China"),
+            Nation::ANZAC => write!(f, "ANZAC"),
+            Nation::France => write!(f, "France"),
+        }
+    }
+}
 
-    let cli = AppArgs::parse();
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Alliance {
+    Axis,
+    Allies,
+}
 
-    let state = match cli.command {
-        Commands::Setup { initial_icp } => Some(GameState::new(initial_icp)),
-        Commands::Status => {
-            show_status();
-            None
-        },
-        Commands::Purchase { troop, ammount } => add_purchase(troop, ammount),
-        Commands::Remove { troop, ammount } => remove_purchase(troop, ammount),
-        Commands::Commit { ipc } => commit_purchase(ipc),
-    };
+#[derive(ValueEnum, Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Category {
+    Land,
+    Air,
+    Sea,
+}
 
-    if let Some(state) = state {
-        save(state);
+impl Category {
+    /// The name of the branch of the military fielding this category
+    const fn branch(&self) -> &'static str {
+        match self {
+            Category::Land => "Army",
+            Category::Air => "Airforce",
+            Category::Sea => "Navy",
+        }
     }
 }
 
-fn show_status() {
-    if let Some(state) = load() {
-        println!("{state}")
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Category::Land => write!(f, "Land"),
+            Category::Air => write!(f, "Air"),
+            Category::Sea => write!(f, "Sea"),
+        }
     }
 }
 
-fn add_purchase(troop: Troops, ammount: i32) -> Option<GameState> {
-    load().map(|mut state|{
-        state.purchases.insert(troop, state.purchases.get(&troop).unwrap_or(&0) + ammount);
-        println!("Added a purchase of {} {}s for {}", ammount, troop, troop.get_cost() * ammount);
-        println!("Remaining IPC: {}", state.ipc - state.get_total_cost());
-        state
-    })
+/// Every nation taking part in a game, stored together in one file
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Game {
+    /// Games saved before the format was versioned have no version and count as 0
+    #[serde(default)]
+    version: u32,
+    nations: BTreeMap<Nation, GameState>,
 }
 
-fn remove_purchase(troop: Troops, ammount: Option<i32>) -> Option<GameState> {
-    load().map(|mut state| {
-        match ammount {
-            Some(ammount) => {
-                state.purchases.insert(troop, state.purchases.get(&troop).unwrap_or(&0) - ammount);
-                println!("Removing {ammount} {troop}s from purchase")
-            }
-            None => {
-                state.purchases.insert(troop, 0);
-                println!("Removing all {troop}s from purchase")
-            }
-        };
-
-        if state.purchases[&troop] <= 0 {
-            state.purchases.remove(&troop);
+impl Default for Game {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            nations: BTreeMap::default(),
         }
-
-        state
-    })
+    }
 }
 
-fn commit_purchase(new_ipc: IPC) -> Option<GameState> {
-    match load() {
-        Some(mut state) => {
-            let remaining_ipc = state.ipc - state.get_total_cost();
-            if remaining_ipc >= 0 {
-                println!("commiting purchases...");
-                state.purchases.clear();
+impl Game {
+    /// Games from before multiple nations were supported only tracked Germany
+    fn from_single_nation(state: GameState) -> Self {
+        info!("Migrating single nation game to {}", Nation::Germany);
+
+        Self {
+            version: 0,
+            nations: BTreeMap::from([(Nation::Germany, state)]),
+        }
+    }
 
-                println!("IPC remaining {remaining_ipc}");
+    /// Upgrades a game saved by an older version of the tracker to the current format
+    fn migrate(mut self) -> std::result::Result<Self, String> {
+        if self.version > VERSION {
+            return Err(format!(
+                "it was saved in format version {} but only versions up to {VERSION} are supported",
+                self.version
+            ));
+        }
 
-                state.ipc = remaining_ipc + new_ipc;
-                println!("New IPC total {}", state.ipc);
+        if self.version == 0 {
+            // Every field added before versioning has a default, so the game only needs its version stamped
+            progress!("Upgraded the game from format version 0 to 1");
+            self.version = 1;
+        }
 
-                Some(state)
-            } else {
-                println!("You don't have enough IPC to pay for your purchases");
-                None
+        if self.version == 1 {
+            // Games without a round counter are on the round after the last one committed
+            for state in self.nations.values_mut() {
+                state.round = state.history.len() as u32 + 1;
             }
-        },
-        None => None,
+            progress!("Upgraded the game from format version 1 to 2, counting the rounds from the history");
+            self.version = 2;
+        }
+
+        Ok(self)
     }
 }
 
-fn load() -> Option<GameState> {
-    let file = OpenOptions::new().read(true).open(FILE);
+/// The economy of a single nation
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct GameState {
+    ipc: IPC,
+    purchases: BTreeMap<Troops, i32>,
+    #[serde(default)]
+    history: Vec<RoundRecord>,
+    #[serde(default)]
+    edition: Edition,
+    /// IPC spent this round on things other than troops
+    #[serde(default)]
+    expenses: Vec<Expense>,
+    /// The most of each troop type that may be bought in a round
+    #[serde(default)]
+    caps: BTreeMap<Troops, i32>,
+    /// The most units of each category that may be committed in a round
+    #[serde(default)]
+    category_caps: BTreeMap<Category, i32>,
+    /// The cost of every purchase committed this game
+    #[serde(default)]
+    total_spent: IPC,
+    /// The round currently being played
+    #[serde(default = "first_round")]
+    round: u32,
+    /// Research tokens bought that haven't made a breakthrough yet
+    #[serde(default)]
+    tech_tokens: u32,
+    #[serde(default)]
+    techs: Vec<Tech>,
+    /// IPC put aside for later rounds, which isn't part of `ipc` until it is unbanked
+    #[serde(default)]
+    banked: IPC,
+    /// The income commit uses when none is given
+    #[serde(default)]
+    base_income: Option<IPC>,
+    /// The purchases and removals made this round, in the order they were made
+    #[serde(default)]
+    actions: Vec<Action>,
+    /// The units on the board, which committed purchases are added to
+    #[serde(default)]
+    units: BTreeMap<Troops, i32>,
+}
+
+fn first_round() -> u32 {
+    1
+}
 
-    match file {
-        Ok(file) => from_reader(file).map_or_else(
-            |e| {
-                error!("Failed to load game state from file due to error {e:?}");
-                None
-            },
-            |state| Some(state),
-        ),
-        Err(e) => {
-            error!("Failed to load game state from file due to error {e:?}");
-            None
+impl GameState {
+    fn new(ipc: IPC, edition: Edition) -> Self {
+        Self {
+            ipc,
+            purchases: BTreeMap::default(),
+            history: Vec::default(),
+            edition,
+            expenses: Vec::default(),
+            caps: BTreeMap::default(),
+            category_caps: BTreeMap::default(),
+            total_spent: 0,
+            round: first_round(),
+            tech_tokens: 0,
+            techs: Vec::default(),
+            banked: 0,
+            base_income: None,
+            actions: Vec::default(),
+            units: BTreeMap::default(),
         }
     }
-}
 
-fn save(state: GameState) {
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(FILE);
+    fn get_cost(&self, troop: Troops) -> IPC {
+        troop.cost_in(self.edition)
+    }
 
-    match file {
-        Ok(file) => {
-            if let Err(e) = to_writer_pretty(file, &state, PrettyConfig::default()) {
-                error!("Failed to save state due to err: {e:?}")
-            };
-        }
-        Err(e) => error!("Failed to save state due to err: {e:?}"),
-    };
-}
\ No newline at end of file
+    /// How many more of each troop of the edition, or just of `category`, the remaining ipc buys
+    fn affordable(&self, category: Option<Category>) -> Option<BTreeMap<Troops, i32>> {
+        let remaining_ipc = self.get_remaining_ipc()?.max(0);
+
+        Some(
+            Troops::value_variants()
+                .iter()
+                .copied()
+                .filter(|troop| troop.available_in(self.edition))
+                .filter(|troop| category.is_none_or(|category| troop.category() == category))
+                .map(|troop| (troop, remaining_ipc / self.get_cost(troop)))
+                .collect(),
+        )
+    }
+
+    /// The cost of `ammount` troops, or `None` if it overflows
+    fn get_subtotal(&self, troop: Troops, ammount: i32) -> Option<IPC> {
+        self.get_cost(troop).checked_mul(ammount)
+    }
+
+    /// The cost of all purchases, or `None` if it overflows
+    fn get_total_cost(&self) -> Option<IPC> {
+        self.purchases.iter()
+            .try_fold(0, |acc: IPC, (troop, ammount)| acc.checked_add(self.get_subtotal(*troop, *ammount)?))
+    }
+
+    fn get_category_cost(&self, category: Category) -> Option<IPC> {
+        self.purchases.iter()
+            .filter(|(troop, _)| troop.category() == category)
+            .try_fold(0, |acc: IPC, (troop, ammount)| acc.checked_add(self.get_subtotal(*troop, *ammount)?))
+    }
+
+    /// The number of troops of the category being purchased
+    fn get_category_count(&self, category: Category) -> Option<i32> {
+        self.purchases.iter()
+            .filter(|(troop, _)| troop.category() == category)
+            .try_fold(0, |acc: i32, (_, ammount)| acc.checked_add(*ammount))
+    }
+
+    /// The total unit value of the units on the board, or `None` if it overflows
+    fn get_army_value(&self) -> Option<IPC> {
+        self.units.iter()
+            .try_fold(0, |acc: IPC, (troop, ammount)| acc.checked_add(self.get_subtotal(*troop, *ammount)?))
+    }
+
+    fn get_remaining_ipc(&self) -> Option<IPC> {
+        self.ipc.checked_sub(self.get_total_cost()?)
+    }
+
+    /// Removes the purchases of no or a negative number of troops, returning how many there were
+    fn purge_zeros(&mut self) -> usize {
+        let before = self.purchases.len();
+        self.purchases.retain(|_, ammount| *ammount > 0);
+        before - self.purchases.len()
+    }
+
+    /// How many more of `troop` its cap and the cap of its category allow on top of the purchases and `additions`
+    ///
+    /// `None` if neither is capped.
+    fn cap_room(&self, troop: Troops, additions: &BTreeMap<Troops, i32>) -> Option<i32> {
+        let count = |troop: &Troops| {
+            self.purchases.get(troop).unwrap_or(&0).saturating_add(*additions.get(troop).unwrap_or(&0))
+        };
+
+        let troop_room = self.caps.get(&troop).map(|max| max.saturating_sub(count(&troop)));
+        let category_room = self.category_caps.get(&troop.category()).map(|max| {
+            let bought = Troops::value_variants()
+                .iter()
+                .filter(|other| other.category() == troop.category())
+                .map(count)
+                .fold(0, i32::saturating_add);
+            max.saturating_sub(bought)
+        });
+
+        match (troop_room, category_room) {
+            (Some(troop_room), Some(category_room)) => Some(troop_room.min(category_room)),
+            (room, None) | (None, room) => room,
+        }
+    }
+
+    /// The categories more units are purchased of than their cap allows
+    fn category_cap_violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        for category in Category::value_variants() {
+            let Some(max) = self.category_caps.get(category) else {
+                continue;
+            };
+
+            match self.get_category_count(*category) {
+                Some(count) if count > *max => {
+                    violations.push(format!("{count} {category} units are purchased, but at most {max} can be bought each round"))
+                }
+                Some(_) => (),
+                None => violations.push(format!("The number of {category} units overflowed")),
+            }
+        }
+
+        violations
+    }
+}
+
+/// How the status of a game is rendered
+#[derive(Clone, Copy)]
+struct RenderOptions {
+    /// Highlight the output with terminal colors
+    color: bool,
+    /// Use the old untabulated listing of purchases
+    plain: bool,
+    /// List the purchases and removals made this round
+    actions: bool,
+    /// The order the purchases are listed in
+    sort: PurchaseOrder,
+    /// How many of the last rounds the income trend covers
+    window: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            color: false,
+            plain: false,
+            actions: false,
+            sort: PurchaseOrder::default(),
+            window: TREND_WINDOW,
+        }
+    }
+}
+
+impl GameState {
+    /// Renders the status of the game as configured by `options`
+    fn render(&self, options: RenderOptions) -> String {
+        let mut status = String::new();
+        // Writing to a String can't fail
+        let _ = self.write_status(&mut status, options);
+        status
+    }
+
+    fn write_status(&self, f: &mut dyn std::fmt::Write, options: RenderOptions) -> std::fmt::Result {
+        let currency = currency();
+        let color = options.color;
+
+        writeln!(f, "Round {}", self.round)?;
+        self.write_income_trend(f, options.window)?;
+        writeln!(f, "Current game state ({}):", self.edition)?;
+        writeln!(f, "Purchases:")?;
+
+        if options.plain {
+            for (troop, ammount) in self.sorted_purchases(options.sort).iter() {
+                writeln!(
+                    f,
+                    "\t{} : {} á {} (A/D/M {}/{}/{})",
+                    paint(troop, Style::new().bold(), color),
+                    ammount,
+                    paint(format!("{} {currency}", self.get_cost(*troop)), Style::new().yellow(), color),
+                    troop.attack(),
+                    troop.defense(),
+                    troop.movement()
+                )?;
+            }
+        } else if !self.purchases.is_empty() {
+            self.purchase_table(options.sort).write(f, color)?;
+        }
+
+        let (Some(cost), Some(remaining_ipc)) = (self.get_total_cost(), self.get_remaining_ipc()) else {
+            return writeln!(f, "{}", paint(OVERFLOW, Style::new().red().bold(), color));
+        };
+
+        for category in Category::value_variants() {
+            match (self.get_category_count(*category), self.get_category_cost(*category)) {
+                (Some(count), Some(cost)) => writeln!(f, "{category}: {count} units ({cost} {currency})")?,
+                _ => writeln!(f, "{category}: overflowed")?,
+            }
+        }
+
+        let remaining_style = if remaining_ipc < 0 {
+            Style::new().red().bold()
+        } else {
+            Style::new().green().bold()
+        };
+
+        for expense in self.expenses.iter() {
+            writeln!(f, "Spent {expense}")?;
+        }
+
+        if !self.techs.is_empty() {
+            let techs: Vec<String> = self.techs.iter().map(Tech::to_string).collect();
+            writeln!(f, "Technologies: {}", techs.join(", "))?;
+        }
+
+        if self.tech_tokens > 0 {
+            writeln!(f, "Research tokens: {}", self.tech_tokens)?;
+        }
+
+        if self.banked > 0 {
+            writeln!(f, "Banked {currency}: {}", self.banked)?;
+        }
+
+        if let Some(base_income) = self.base_income {
+            writeln!(f, "Income each round: {base_income} {currency}")?;
+        }
+
+        if !self.units.is_empty() {
+            let count: i64 = self.units.values().map(|ammount| i64::from(*ammount)).sum();
+            match self.get_army_value() {
+                Some(value) => writeln!(f, "Units on the board: {count} worth {value} {currency}")?,
+                None => writeln!(f, "Units on the board: {count} worth more {currency} than can be counted")?,
+            }
+        }
+
+        if let Some(last) = self.history.last() {
+            writeln!(
+                f,
+                "Income last round: {} {currency}{} + {} {currency} national objectives",
+                last.income,
+                last.source_breakdown(),
+                last.bonus
+            )?;
+            writeln!(f, "Spent on purchases this game: {} {currency}", self.total_spent)?;
+        }
+
+        writeln!(f, "At a total cost of {}", paint(format!("{cost} {currency}"), Style::new().yellow(), color))?;
+        writeln!(f, "{}", paint(format!("Remaining {currency}: {remaining_ipc}"), remaining_style, color))?;
+
+        if options.actions {
+            self.write_actions(f)?;
+        }
+
+        Ok(())
+    }
+
+    /// The round, IPC, pending purchases and units on the board in a single line
+    fn render_compact(&self) -> String {
+        let currency = currency();
+        let pending = match (self.get_total_cost(), self.get_remaining_ipc()) {
+            (Some(cost), Some(remaining_ipc)) => format!("Pending {cost} ({remaining_ipc} left)"),
+            _ => "Pending overflowed".to_string(),
+        };
+        let units: i64 = self.units.values().map(|ammount| i64::from(*ammount)).sum();
+
+        format!("R{} | {currency} {} | {pending} | {units} units", self.round, self.ipc)
+    }
+
+    /// The total income of the last `window` rounds and their average, or of all rounds if there are fewer
+    fn write_income_trend(&self, f: &mut dyn std::fmt::Write, window: usize) -> std::fmt::Result {
+        let currency = currency();
+        let recent = &self.history[self.history.len().saturating_sub(window)..];
+        if recent.is_empty() {
+            return Ok(());
+        }
+
+        let incomes: Vec<String> = recent.iter().map(|record| record.total_income().to_string()).collect();
+        let average = recent.iter().map(RoundRecord::total_income).sum::<i64>() as f64 / recent.len() as f64;
+        let rounds = if recent.len() == 1 { "round".to_string() } else { format!("{} rounds", recent.len()) };
+
+        writeln!(f, "Income last {rounds}: {} (avg {average:.1} {currency})", incomes.join(", "))
+    }
+
+    /// Renders the purchases and removals made this round, in the order they were made
+    fn render_actions(&self) -> String {
+        let mut output = String::new();
+        // Writing to a String can't fail
+        let _ = self.write_actions(&mut output);
+        output
+    }
+
+    fn write_actions(&self, f: &mut dyn std::fmt::Write) -> std::fmt::Result {
+        if self.actions.is_empty() {
+            return writeln!(f, "No purchases have been made this round");
+        }
+
+        writeln!(f, "Purchases this round:")?;
+        for (number, action) in self.actions.iter().enumerate() {
+            writeln!(f, "\t{}. {action}", number + 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Finds everything wrong with the state, as errors that break it and warnings that are allowed but suspicious
+    fn validate(&self) -> (Vec<String>, Vec<String>) {
+        let currency = currency();
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+
+        for (troop, ammount) in self.purchases.iter() {
+            if *ammount < 0 {
+                errors.push(format!("{ammount} {troop}s are purchased, purchases can't be negative"));
+            } else if *ammount == 0 {
+                errors.push(format!("{troop}s are listed with no purchases"));
+            }
+
+            if !troop.available_in(self.edition) {
+                errors.push(format!("{troop}s are purchased but can't be bought in {}", self.edition));
+            }
+        }
+
+        for (troop, ammount) in self.units.iter() {
+            if *ammount < 0 {
+                errors.push(format!("{ammount} {troop}s are on the board, units can't be negative"));
+            }
+        }
+
+        for (troop, max) in self.caps.iter() {
+            if *max < 0 {
+                errors.push(format!("{troop}s have a negative cap of {max}"));
+            }
+        }
+
+        for (category, max) in self.category_caps.iter() {
+            if *max < 0 {
+                errors.push(format!("{category} units have a negative cap of {max}"));
+            }
+        }
+        warnings.extend(self.category_cap_violations());
+
+        if self.ipc < 0 {
+            errors.push(format!("The {currency} is negative: {}", self.ipc));
+        }
+
+        if self.banked < 0 {
+            errors.push(format!("The banked {currency} is negative: {}", self.banked));
+        }
+
+        match self.get_remaining_ipc() {
+            Some(remaining_ipc) if remaining_ipc < 0 => {
+                warnings.push(format!("The purchases exceed the {currency} by {}", -remaining_ipc))
+            }
+            Some(_) => {}
+            None => errors.push(OVERFLOW.to_string()),
+        }
+
+        (errors, warnings)
+    }
+
+    /// Renders the purchases as a markdown table followed by the totals as a list
+    fn render_markdown(&self, sort: PurchaseOrder) -> String {
+        let mut output = String::new();
+        // Writing to a String can't fail
+        let _ = self.write_markdown(&mut output, sort);
+        output
+    }
+
+    fn write_markdown(&self, f: &mut dyn std::fmt::Write, sort: PurchaseOrder) -> std::fmt::Result {
+        let currency = currency();
+        if self.purchases.is_empty() {
+            writeln!(f, "No purchases this round")?;
+        } else {
+            self.purchase_table(sort).write_markdown(f)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "- Round: {}", self.round)?;
+        writeln!(f, "- Edition: {}", self.edition)?;
+
+        let (Some(cost), Some(remaining_ipc)) = (self.get_total_cost(), self.get_remaining_ipc()) else {
+            return writeln!(f, "- {OVERFLOW}");
+        };
+
+        for category in Category::value_variants() {
+            match (self.get_category_count(*category), self.get_category_cost(*category)) {
+                (Some(count), Some(cost)) => writeln!(f, "- {category}: {count} units ({cost} {currency})")?,
+                _ => writeln!(f, "- {category}: overflowed")?,
+            }
+        }
+
+        for expense in self.expenses.iter() {
+            writeln!(f, "- Spent {expense}")?;
+        }
+
+        if self.banked > 0 {
+            writeln!(f, "- Banked {currency}: {}", self.banked)?;
+        }
+
+        writeln!(f, "- Total cost: {cost} {currency}")?;
+        writeln!(f, "- Remaining {currency}: {remaining_ipc}")
+    }
+
+    /// The purchases in `order`, troops that are tied stay in the order of the enum
+    fn sorted_purchases(&self, order: PurchaseOrder) -> Vec<(Troops, i32)> {
+        let mut purchases: Vec<(Troops, i32)> = self.purchases.iter().map(|(troop, ammount)| (*troop, *ammount)).collect();
+
+        match order {
+            PurchaseOrder::Troop => (),
+            PurchaseOrder::Cost => purchases.sort_by_key(|(troop, _)| std::cmp::Reverse(self.get_cost(*troop))),
+            PurchaseOrder::Name => purchases.sort_by_cached_key(|(troop, _)| troop.to_string()),
+            PurchaseOrder::Amount => purchases.sort_by_key(|(_, ammount)| std::cmp::Reverse(*ammount)),
+        }
+
+        purchases
+    }
+
+    fn purchase_table(&self, order: PurchaseOrder) -> Table {
+        let mut table = Table::new()
+            .styled_column("Troop", Align::Left, Style::new().bold())
+            .column("Amount", Align::Right)
+            .styled_column("Cost", Align::Right, Style::new().yellow())
+            .styled_column("Subtotal", Align::Right, Style::new().yellow())
+            .column("A/D/M", Align::Right);
+
+        for (troop, ammount) in self.sorted_purchases(order) {
+            table.add_row(vec![
+                troop.to_string(),
+                ammount.to_string(),
+                self.get_cost(troop).to_string(),
+                self.get_subtotal(troop, ammount).map_or_else(|| "overflowed".to_string(), |cost| cost.to_string()),
+                format!("{}/{}/{}", troop.attack(), troop.defense(), troop.movement()),
+            ]);
+        }
+
+        table
+    }
+}
+
+impl Display for GameState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_status(f, RenderOptions::default())
+    }
+}
+
+/// Applies `style` to `text` if `color` is set
+fn paint(text: impl Display, style: Style, color: bool) -> String {
+    if color {
+        text.style(style).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// The game state along with the values derived from it, used for machine readable output
+#[derive(Serialize)]
+struct StatusReport<'a> {
+    #[serde(flatten)]
+    state: &'a GameState,
+    total_cost: IPC,
+    remaining_ipc: IPC,
+    category_costs: BTreeMap<Category, IPC>,
+    category_counts: BTreeMap<Category, i32>,
+    army_value: IPC,
+}
+
+impl<'a> StatusReport<'a> {
+    /// Returns `None` if any of the derived totals overflow
+    fn new(state: &'a GameState) -> Option<Self> {
+        Some(Self {
+            state,
+            total_cost: state.get_total_cost()?,
+            remaining_ipc: state.get_remaining_ipc()?,
+            category_costs: Category::value_variants()
+                .iter()
+                .map(|category| Some((*category, state.get_category_cost(*category)?)))
+                .collect::<Option<_>>()?,
+            category_counts: Category::value_variants()
+                .iter()
+                .map(|category| Some((*category, state.get_category_count(*category)?)))
+                .collect::<Option<_>>()?,
+            army_value: state.get_army_value()?,
+        })
+    }
+}
+
+/// How the state of a nation differs between two games
+#[derive(Serialize)]
+struct StateDiff {
+    nation: Nation,
+    ipc: Change,
+    purchases: Vec<TroopChange>,
+}
+
+impl StateDiff {
+    fn new(nation: Nation, a: &GameState, b: &GameState) -> Self {
+        let purchases = Troops::value_variants()
+            .iter()
+            .map(|troop| TroopChange {
+                troop: *troop,
+                change: Change::new(*a.purchases.get(troop).unwrap_or(&0), *b.purchases.get(troop).unwrap_or(&0)),
+            })
+            .filter(|change| change.change.before != 0 || change.change.after != 0)
+            .collect();
+
+        Self {
+            nation,
+            ipc: Change::new(a.ipc, b.ipc),
+            purchases,
+        }
+    }
+}
+
+impl Display for StateDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let currency = currency();
+        writeln!(f, "{}", self.nation)?;
+        writeln!(f, "{currency}: {}", self.ipc)?;
+
+        if self.purchases.is_empty() {
+            writeln!(f, "No purchases in either game")?;
+        } else {
+            writeln!(f, "Purchases:")?;
+            for change in &self.purchases {
+                writeln!(f, "\t{} : {}", change.troop, change.change)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct TroopChange {
+    troop: Troops,
+    #[serde(flatten)]
+    change: Change,
+}
+
+#[derive(Serialize)]
+struct Change {
+    before: i32,
+    after: i32,
+    delta: i64,
+}
+
+impl Change {
+    fn new(before: i32, after: i32) -> Self {
+        Self {
+            before,
+            after,
+            delta: after as i64 - before as i64,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {} ({:+})", self.before, self.after, self.delta)
+    }
+}
+
+/// A committed round of purchases
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct RoundRecord {
+    round: u32,
+    purchases: BTreeMap<Troops, i32>,
+    cost: IPC,
+    /// The IPC total after the round was committed
+    ipc: IPC,
+    /// The income from territories
+    #[serde(default)]
+    income: IPC,
+    /// The income from national objectives
+    #[serde(default)]
+    bonus: IPC,
+    #[serde(default)]
+    expenses: Vec<Expense>,
+    /// When the round was committed, rounds committed before this was recorded have none
+    #[serde(default)]
+    committed_at: Option<DateTime<Utc>>,
+    /// Where the income came from, if that was given
+    #[serde(default)]
+    sources: Vec<IncomeSource>,
+}
+
+/// A single purchase or removal made during the current round
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Action {
+    troop: Troops,
+    /// How many troops were added, negative for removals
+    delta: i32,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.delta < 0 {
+            write!(f, "Removed {} {}s", -self.delta, self.troop)
+        } else {
+            write!(f, "Added {} {}s", self.delta, self.troop)
+        }
+    }
+}
+
+/// IPC spent directly rather than on troops
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Expense {
+    ipc: IPC,
+    reason: Option<String>,
+    /// The troop type repaired, if the IPC was spent on repairs
+    #[serde(default)]
+    repaired: Option<Troops>,
+}
+
+/// A named part of the income of a round, such as territories or convoy routes
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+struct IncomeSource {
+    name: String,
+    ipc: IPC,
+}
+
+impl Display for IncomeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.ipc)
+    }
+}
+
+impl Display for Expense {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let currency = currency();
+        match (self.repaired, &self.reason) {
+            (Some(troop), _) => write!(f, "{} {currency} on repairing {troop}s", self.ipc),
+            (None, Some(reason)) => write!(f, "{} {currency} on {reason}", self.ipc),
+            (None, None) => write!(f, "{} {currency}", self.ipc),
+        }
+    }
+}
+
+impl RoundRecord {
+    /// The sources of the income in parentheses, or nothing if they weren't given
+    fn source_breakdown(&self) -> String {
+        if self.sources.is_empty() {
+            return String::new();
+        }
+
+        let sources: Vec<String> = self.sources.iter().map(IncomeSource::to_string).collect();
+        format!(" ({})", sources.join(", "))
+    }
+
+    /// The income from territories and national objectives together
+    fn total_income(&self) -> i64 {
+        self.income as i64 + self.bonus as i64
+    }
+
+    /// The cost of the purchases along with everything else spent in the round
+    fn total_spent(&self) -> i64 {
+        self.cost as i64 + self.expenses.iter().map(|expense| expense.ipc as i64).sum::<i64>()
+    }
+}
+
+impl Display for RoundRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let currency = currency();
+        match self.committed_at {
+            Some(committed_at) => writeln!(f, "Round {} ({}):", self.round, committed_at.format("%Y-%m-%d %H:%M UTC"))?,
+            None => writeln!(f, "Round {}:", self.round)?,
+        }
+
+        for (troop, ammount) in self.purchases.iter() {
+            writeln!(f, "\t{} : {}", troop, ammount)?;
+        }
+
+        writeln!(f, "Spent {} {currency}", self.cost)?;
+        for expense in self.expenses.iter() {
+            writeln!(f, "Also spent {expense}")?;
+        }
+        writeln!(f, "Income: {} {currency}{}", self.income, self.source_breakdown())?;
+        writeln!(f, "National objective income: {} {currency}", self.bonus)?;
+        writeln!(f, "New {currency} total: {}", self.ipc)?;
+
+        Ok(())
+    }
+}
+
+/// Exits with 0 if the command succeeded, 1 if it failed and 2 if the arguments were invalid
+fn main() -> ExitCode {
+    let cli = AppArgs::parse();
+    init_logger(cli.options.verbose);
+    let piped = cli.options.file.as_deref().is_some_and(is_stdio);
+    QUIET.store(cli.options.quiet || piped, Ordering::Relaxed);
+    let config = Config::load();
+    if let Some(label) = cli.options.currency.clone().or(config.currency.clone()) {
+        let _ = CURRENCY.set(label);
+    }
+    if let Some(lang) = cli.options.lang.or(config.lang) {
+        let _ = LANG.set(lang);
+    }
+    let _ = SAVE_STYLE.set(SaveStyle {
+        compact: cli.options.compact || config.compact.unwrap_or(false),
+        indent: config.indent.unwrap_or(DEFAULT_INDENT),
+        struct_names: config.struct_names.unwrap_or(false),
+    });
+    PURGE_ON_LOAD.store(config.purge_zeros.unwrap_or(false), Ordering::Relaxed);
+    debug!("axsis_and_allies_trecker");
+
+    exit_code(run(&cli.options, cli.command))
+}
+
+/// Succeeds only if the command did, reporting why it failed otherwise
+fn exit_code(result: Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            report(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Logs failures of the system, the others are the user's to fix so they are just printed
+///
+/// A missing game goes to stderr, so the guidance isn't mistaken for output when piping.
+fn report(e: &AppError) {
+    match e {
+        e if e.is_internal() => error!("{e}"),
+        AppError::FileNotFound(_) => eprintln!("{e}"),
+        e => println!("{e}"),
+    }
+}
+
+fn overflow() -> AppError {
+    AppError::Overflow(OVERFLOW.to_string())
+}
+
+/// Logs errors by default, or whatever RUST_LOG asks for unless `verbose` raises the level
+fn init_logger(verbose: u8) {
+    let mut logger = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("error"));
+
+    let level = match verbose {
+        0 => None,
+        1 => Some(log::LevelFilter::Info),
+        2 => Some(log::LevelFilter::Debug),
+        _ => Some(log::LevelFilter::Trace),
+    };
+    if let Some(level) = level {
+        logger.filter_level(level);
+    }
+
+    logger.init();
+}
+
+/// Runs a single command, saving the nation's state if the command changed it
+fn run(cli: &Options, command: Commands) -> Result<()> {
+    let config = Config::load();
+    let file = state_file(cli.file.as_deref());
+    let dry_run = command.is_dry_run();
+    if let Some(name) = command.writes_directly().filter(|_| cli.no_persist) {
+        return Err(AppError::Refused(format!("{name} always changes files, so it can't be used with --no-persist")));
+    }
+
+    // Commands that change the state return it to be saved, the others return nothing
+    let state: Result<Option<GameState>> = match command {
+        Commands::Setup { initial_ipc, edition, force } => {
+            setup(&file, cli.nation, initial_ipc, edition.or(config.edition).unwrap_or_default(), force).map(Some)
+        },
+        Commands::Status { all, format, json, compact, plain, actions, sort, window } => {
+            let options = RenderOptions {
+                color: use_color(cli.no_color),
+                plain,
+                actions,
+                sort,
+                window,
+            };
+            let nation = if all { None } else { Some(cli.nation) };
+            let format = match (json, compact) {
+                (true, _) => StatusFormat::Json,
+                (_, true) => StatusFormat::Compact,
+                _ => format,
+            };
+            show_status(&file, nation, format, options).map(|()| None)
+        },
+        Commands::Purchase { troop, ammount, buy, max, .. } => {
+            let purchases = match troop {
+                Some(troop) => vec![(troop, ammount)],
+                None => buy,
+            };
+            let cap = troop.zip(max);
+            add_purchase(&file, cli.nation, &purchases, cap, cli.strict).map(Some)
+        },
+        Commands::Whatif { buy } => show_whatif(&file, cli.nation, &buy).map(|()| None),
+        Commands::Remove { troop, ammount, all, .. } => {
+            let ammount = if all { None } else { Some(ammount.unwrap_or(1)) };
+            remove_purchase(&file, cli.nation, troop, ammount).map(Some)
+        },
+        Commands::Commit { ipc, delta, source, bonus, skip_build, yes } => {
+            commit_purchase(&file, cli.nation, ipc, delta, source, bonus, skip_build, yes).map(Some)
+        },
+        Commands::SetIncome { ipc } => set_income(&file, cli.nation, ipc).map(Some),
+        Commands::Spend { ipc, reason } => spend(&file, cli.nation, Expense { ipc, reason, repaired: None }).map(Some),
+        Commands::Repair { troop, ipc } => repair(&file, cli.nation, troop, ipc).map(Some),
+        Commands::Bank { ipc } => bank(&file, cli.nation, ipc).map(Some),
+        Commands::Unbank { ipc } => unbank(&file, cli.nation, ipc).map(Some),
+        Commands::Tech { tokens, roll, seed } => research(&file, cli.nation, tokens, roll, seed).map(Some),
+        Commands::Reset { yes } => reset_purchases(&file, cli.nation, yes).map(Some),
+        Commands::Convert { to, reprice } => convert(&file, cli.nation, to, reprice, cli.strict).map(Some),
+        Commands::Army { add, remove } => {
+            if add.is_empty() && remove.is_empty() {
+                show_army(&file, cli.nation).map(|()| None)
+            } else {
+                update_army(&file, cli.nation, &add, &remove).map(Some)
+            }
+        },
+        Commands::Lose { troop, ammount, clamp } => lose_units(&file, cli.nation, troop, ammount, clamp).map(Some),
+        Commands::Caps { max_land, max_air, max_sea, clear } => {
+            let caps = [(Category::Land, max_land), (Category::Air, max_air), (Category::Sea, max_sea)];
+            let caps: Vec<(Category, i32)> = caps.into_iter().filter_map(|(category, max)| Some((category, max?))).collect();
+
+            if caps.is_empty() && !clear {
+                show_category_caps(&file, cli.nation).map(|()| None)
+            } else {
+                set_category_caps(&file, cli.nation, &caps, clear).map(Some)
+            }
+        },
+        Commands::PurgeZeros => purge_zeros(&file, cli.nation).map(Some),
+        Commands::Undo => undo(&file).map(|()| None),
+        Commands::History { since, last } => show_history(&file, cli.nation, since, last).map(|()| None),
+        Commands::Forecast { rounds } => show_forecast(&file, cli.nation, rounds).map(|()| None),
+        Commands::Summary { chart } => show_summary(&file, cli.nation, chart).map(|()| None),
+        Commands::Info { troop, edition } => {
+            show_info(troop, edition.or(config.edition).unwrap_or_default());
+            Ok(None)
+        },
+        Commands::Cost { troops, total, edition } => {
+            show_costs(&troops, total, edition.or(config.edition).unwrap_or_default()).map(|()| None)
+        },
+        Commands::List { category, edition, aliases, sort } => {
+            show_list(category, edition.or(config.edition).unwrap_or_default(), aliases, sort);
+            Ok(None)
+        },
+        Commands::BuyMax { troop } => buy_max(&file, cli.nation, troop, cli.strict).map(Some),
+        Commands::Afford { category, json } => show_affordable(&file, cli.nation, category, json).map(|()| None),
+        Commands::Top { limit } => show_top(&file, cli.nation, limit).map(|()| None),
+        Commands::Optimize { category } => suggest_purchases(&file, cli.nation, category).map(|()| None),
+        Commands::Export { format, out } => export(&file, format, out.as_deref()).map(|()| None),
+        Commands::Import { path, format } => import(&file, &path, format).map(|()| None),
+        Commands::Battle {
+            attackers,
+            defenders,
+            iterations,
+            techs,
+            defender_techs,
+            seed,
+            report,
+            low_luck,
+            casualty_order,
+            priority,
+            retreat_at,
+        } => {
+            let (techs, edition) = if techs {
+                let state = load(&file, cli.nation)?;
+                (state.techs, state.edition)
+            } else {
+                (Vec::new(), config.edition.unwrap_or_default())
+            };
+
+            let losses = match casualty_order {
+                CasualtyOrder::Cost => LossOrder::Cost,
+                CasualtyOrder::KeepInfantry => LossOrder::KeepInfantry,
+                CasualtyOrder::Custom => LossOrder::Custom(&priority),
+            };
+
+            let rules = Rules {
+                low_luck,
+                losses,
+                retreat_at,
+                edition,
+            };
+
+            simulate_battle(&attackers, &defenders, [&techs, &defender_techs], iterations, seed, report, &rules)
+                .map(|()| None)
+        },
+        Commands::Roll { dice, hit_on, seed } => {
+            roll_dice(dice, hit_on, seed);
+            Ok(None)
+        },
+        Commands::Totals { axis, allies } => show_totals(&file, &axis, &allies).map(|()| None),
+        Commands::Config { action } => {
+            update_config(config, action).map(|()| None)
+        },
+        Commands::Validate => validate(&file).map(|()| None),
+        Commands::Diff { file_a, file_b, json } => show_diff(&file_a, &file_b, cli.nation, json).map(|()| None),
+        Commands::Game { action } => manage_games(action, cli.file.as_deref()).map(|()| None),
+        Commands::Plan { action } => match action {
+            PlanAction::Save { name } => save_plan(&file, cli.nation, &name).map(|()| None),
+            PlanAction::Load { name } => load_plan(&file, cli.nation, &name, cli.strict).map(Some),
+            PlanAction::List => {
+                show_plans();
+                Ok(None)
+            },
+        },
+        Commands::Interactive => {
+            if is_stdio(&file) {
+                return Err(AppError::Refused(
+                    "An interactive session reads commands from stdin, so it can't read the game from there too".to_string(),
+                ));
+            }
+
+            interactive(cli);
+            Ok(None)
+        },
+        Commands::Batch { path } => run_batch(cli, &path).map(|()| None),
+        Commands::Completions { shell } => {
+            let mut command = AppArgs::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+            Ok(None)
+        },
+    };
+
+    match state? {
+        Some(_) if cli.no_persist => {
+            debug!("Not saving the game to {} since --no-persist is set", file.display());
+            Ok(())
+        },
+        Some(state) if !dry_run => save(&file, cli.nation, state),
+        _ => Ok(()),
+    }
+}
+
+/// The file given with --file, then the active game and otherwise the default file
+fn state_file(file: Option<&Path>) -> PathBuf {
+    file.map(Path::to_path_buf)
+        .or_else(|| profile::active().and_then(|name| profile::path(&name).ok()))
+        .unwrap_or_else(|| PathBuf::from(FILE))
+}
+
+/// Starts, switches between and deletes games
+///
+/// `file` is the file given with --file or AXIS_STATE_FILE, which the commands use over the active game.
+fn manage_games(action: GameAction, file: Option<&Path>) -> Result<()> {
+    let warn_overridden = |name: &str| {
+        if let Some(file) = file {
+            println!(
+                "WARNING: commands keep using {} rather than {name} until --file and AXIS_STATE_FILE are unset",
+                file.display()
+            );
+        }
+    };
+
+    match action {
+        GameAction::New { name } => {
+            let path = profile::path(&name)?;
+            if path.exists() {
+                return Err(AppError::Refused(format!(
+                    "There already is a game called {name}, use game switch to play it"
+                )));
+            }
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(AppError::io(format!("create game directory {}", dir.display())))?;
+            }
+
+            save_game(&path, &Game::default())?;
+            profile::set_active(Some(&name))?;
+            progress!("Started the game {name}, use setup to add nations to it");
+            warn_overridden(&name);
+        }
+        GameAction::Switch { name } => {
+            let path = profile::path(&name)?;
+            if !path.exists() {
+                return Err(AppError::Refused(format!(
+                    "There is no game called {name}, use game list to see the games"
+                )));
+            }
+
+            profile::set_active(Some(&name))?;
+            progress!("Switched to the game {name}");
+            warn_overridden(&name);
+        }
+        GameAction::List => {
+            let names = profile::list();
+            if names.is_empty() {
+                println!("There are no games, use game new to start one");
+            }
+
+            let active = profile::active();
+            for name in names {
+                let marker = if active.as_ref() == Some(&name) { "*" } else { " " };
+                println!("{marker} {name}");
+            }
+        }
+        GameAction::Delete { name, yes } => {
+            let path = profile::path(&name)?;
+            if !path.exists() {
+                return Err(AppError::Refused(format!(
+                    "There is no game called {name}, use game list to see the games"
+                )));
+            }
+
+            if !yes && !confirm_on_stdin(&format!("Delete the game {name}?")) {
+                return Err(AppError::Declined(format!("Keeping the game {name}")));
+            }
+
+            fs::remove_file(&path).map_err(AppError::io(format!("delete {}", path.display())))?;
+            let _ = fs::remove_file(backup_path(&path));
+
+            // Fall back to the default file rather than pointing at a game that is gone
+            if profile::active().as_ref() == Some(&name) {
+                profile::set_active(None)?;
+            }
+            progress!("Deleted the game {name}");
+        }
+    }
+
+    Ok(())
+}
+
+fn setup(file: &Path, nation: Nation, initial_ipc: IPC, edition: Edition, force: bool) -> Result<GameState> {
+    let currency = currency();
+    if initial_ipc < 0 {
+        return Err(AppError::Refused(format!("Can't start out with negative {currency}")));
+    }
+
+    if initial_ipc > SANE_INITIAL_IPC {
+        println!("WARNING: starting out with {initial_ipc} {currency} is far more than any nation has at the start of a game!");
+    }
+
+    let in_progress = game_exists(file) && load_game(file).is_ok_and(|game| game.nations.contains_key(&nation));
+
+    if in_progress && !force {
+        return Err(AppError::Refused(format!(
+            "{nation} already has a game in progress in {}, use --force to overwrite it",
+            file.display()
+        )));
+    }
+
+    Ok(GameState::new(initial_ipc, edition))
+}
+
+/// Colors are used unless turned off by `--no-color` or the NO_COLOR convention
+fn use_color(no_color: bool) -> bool {
+    !no_color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Shows the status of `nation`, or of every nation if it is `None`
+fn show_status(file: &Path, nation: Option<Nation>, format: StatusFormat, options: RenderOptions) -> Result<()> {
+    let mut game = load_game(file)?;
+
+    if let Some(nation) = nation {
+        game.nations.retain(|other, _| *other == nation);
+
+        if game.nations.is_empty() {
+            return Err(AppError::UnknownNation(nation));
+        }
+    }
+
+    match format {
+        StatusFormat::Text => {
+            for (nation, state) in game.nations.iter() {
+                println!("{nation}");
+                println!("{}", state.render(options))
+            }
+        }
+        StatusFormat::Markdown => {
+            for (nation, state) in game.nations.iter() {
+                println!("## {nation}\n");
+                println!("{}", state.render_markdown(options.sort));
+
+                if options.actions {
+                    println!("{}", state.render_actions());
+                }
+            }
+        }
+        StatusFormat::Json => return show_status_json(&game, nation),
+        StatusFormat::Compact => {
+            for (other, state) in game.nations.iter() {
+                // The nation is only needed to tell the lines apart when there are several
+                match nation {
+                    Some(_) => println!("{}", state.render_compact()),
+                    None => println!("{other} | {}", state.render_compact()),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn show_status_json(game: &Game, nation: Option<Nation>) -> Result<()> {
+    let reports: Option<BTreeMap<Nation, StatusReport>> = game.nations
+        .iter()
+        .map(|(nation, state)| Some((*nation, StatusReport::new(state)?)))
+        .collect();
+    let reports = reports.ok_or_else(overflow)?;
+
+    let report = match nation {
+        Some(nation) => serde_json::to_string_pretty(&reports[&nation]),
+        None => serde_json::to_string_pretty(&reports),
+    }
+    .map_err(AppError::format("serialize status"))?;
+
+    println!("{report}");
+    Ok(())
+}
+
+fn show_history(file: &Path, nation: Nation, since: Option<u32>, last: Option<usize>) -> Result<()> {
+    let state = load(file, nation)?;
+
+    if state.history.is_empty() {
+        println!("No rounds have been committed yet");
+        return Ok(());
+    }
+
+    let records = match (since, last) {
+        (Some(since), _) => &state.history[state.history.partition_point(|record| record.round < since)..],
+        (_, Some(last)) => &state.history[state.history.len().saturating_sub(last)..],
+        (None, None) => &state.history[..],
+    };
+
+    if records.is_empty() {
+        // The round counter of a hand edited game may be off, the history itself says which round was committed last
+        let last = state.history.last().map_or(0, |record| record.round);
+        println!("No rounds match, the last round committed was round {last}");
+    }
+
+    for record in records.iter() {
+        println!("{record}");
+    }
+
+    Ok(())
+}
+
+/// The width of the longest bar in the income chart
+const CHART_WIDTH: i64 = 40;
+
+fn show_summary(file: &Path, nation: Nation, chart: bool) -> Result<()> {
+    let currency = currency();
+    let state = load(file, nation)?;
+
+    if state.history.is_empty() {
+        println!("No rounds have been committed yet");
+        return Ok(());
+    }
+
+    let mut table = Table::new()
+        .column("Round", Align::Right)
+        .column("Income", Align::Right)
+        .column("Spent", Align::Right)
+        .column(currency, Align::Right);
+
+    for record in state.history.iter() {
+        table.add_row(vec![
+            record.round.to_string(),
+            record.total_income().to_string(),
+            record.total_spent().to_string(),
+            record.ipc.to_string(),
+        ]);
+    }
+
+    print!("{}", table.render(false));
+
+    let rounds = state.history.len() as i64;
+    let income: i64 = state.history.iter().map(RoundRecord::total_income).sum();
+    let spent: i64 = state.history.iter().map(RoundRecord::total_spent).sum();
+    println!("Average income: {:.1} {currency}", income as f64 / rounds as f64);
+    println!("Total spent: {spent} {currency}");
+
+    // Only the rounds that were timestamped can tell how fast the game is going
+    let dates: Vec<DateTime<Utc>> = state.history.iter().filter_map(|record| record.committed_at).collect();
+    if let [first, .., last] = dates.as_slice() {
+        let pace = (*last - *first) / (dates.len() as i32 - 1);
+        println!("Average time between rounds: {}", format_duration(pace));
+    }
+
+    if chart {
+        let max = state.history.iter().map(RoundRecord::total_income).max().unwrap_or(0).max(1);
+
+        println!("Income per round:");
+        for record in state.history.iter() {
+            let width = record.total_income().max(0) * CHART_WIDTH / max;
+            println!("\t{:>3} | {} {}", record.round, "#".repeat(width as usize), record.total_income());
+        }
+    }
+
+    Ok(())
+}
+
+fn show_forecast(file: &Path, nation: Nation, window: usize) -> Result<()> {
+    let currency = currency();
+    let state = load(file, nation)?;
+    let remaining_ipc = state.get_remaining_ipc().ok_or_else(overflow)?;
+
+    let recent = &state.history[state.history.len().saturating_sub(window)..];
+    let incomes: Vec<f64> = recent.iter().map(|record| record.total_income() as f64).collect();
+
+    let next_income = match incomes.as_slice() {
+        [] => {
+            println!("No rounds have been committed yet, so there is no income to forecast from");
+            return Ok(());
+        }
+        [income] => {
+            println!("Only one round has been committed, so the income is expected to stay at {income} {currency}");
+            *income
+        }
+        _ => {
+            // A least squares line through the incomes, continued one round further
+            let count = incomes.len() as f64;
+            let mean_round = (count - 1.0) / 2.0;
+            let mean_income = incomes.iter().sum::<f64>() / count;
+            let (covariance, variance) =
+                incomes.iter().enumerate().fold((0.0, 0.0), |(covariance, variance), (round, income)| {
+                    let offset = round as f64 - mean_round;
+                    (covariance + offset * (income - mean_income), variance + offset * offset)
+                });
+            let growth = covariance / variance;
+
+            println!("Income trend over the last {} rounds: {growth:+.1} {currency} per round", incomes.len());
+            (mean_income + growth * (count - mean_round)).max(0.0)
+        }
+    };
+
+    println!("Estimated income next round: ~{next_income:.0} {currency}");
+    println!(
+        "At this trend, next round you'll have ~{:.0} {currency}, this is only an estimate",
+        remaining_ipc as f64 + next_income
+    );
+
+    Ok(())
+}
+
+fn format_duration(duration: chrono::Duration) -> String {
+    if duration.num_days() > 0 {
+        format!("{:.1} days", duration.num_hours() as f64 / 24.0)
+    } else if duration.num_hours() > 0 {
+        format!("{:.1} hours", duration.num_minutes() as f64 / 60.0)
+    } else {
+        format!("{} minutes", duration.num_minutes())
+    }
+}
+
+/// Prints every problem with the game, failing if any of them are errors
+fn validate(file: &Path) -> Result<()> {
+    let game = load_game(file)?;
+
+    let mut valid = true;
+    for (nation, state) in game.nations.iter() {
+        let (errors, warnings) = state.validate();
+
+        for error in errors.iter() {
+            println!("{nation}: ERROR: {error}");
+        }
+        for warning in warnings.iter() {
+            println!("{nation}: WARNING: {warning}");
+        }
+
+        valid &= errors.is_empty();
+    }
+
+    if !valid {
+        return Err(AppError::Refused(format!("{} has errors", file.display())));
+    }
+
+    progress!("{} is valid", file.display());
+    Ok(())
+}
+
+fn show_diff(file_a: &Path, file_b: &Path, nation: Nation, json: bool) -> Result<()> {
+    let a = load(file_a, nation)?;
+    let b = load(file_b, nation)?;
+
+    let diff = StateDiff::new(nation, &a, &b);
+    if json {
+        let diff = serde_json::to_string_pretty(&diff).map_err(AppError::format("serialize diff"))?;
+        println!("{diff}");
+    } else {
+        print!("{diff}");
+    }
+
+    Ok(())
+}
+
+fn show_info(troop: Troops, edition: Edition) {
+    let currency = currency();
+    println!("{troop}");
+    println!("\tCost: {} {currency}", troop.cost_in(edition));
+    println!("\tAttack: {}", troop.attack());
+    println!("\tDefense: {}", troop.defense());
+    println!("\tMovement: {}", troop.movement());
+    println!("\tCost per attack: {}", format_efficiency(troop.cost_per_attack_in(edition)));
+    println!("\tCost per defense: {}", format_efficiency(troop.cost_per_defense_in(edition)));
+
+    if !troop.available_in(edition) {
+        println!("\tCan't be bought in {edition}");
+    }
+}
+
+fn show_costs(troops: &[Troops], total: i32, edition: Edition) -> Result<()> {
+    for troop in troops {
+        match troop.cost_in(edition).checked_mul(total) {
+            Some(cost) if troop.available_in(edition) => println!("{troop}: {cost}"),
+            Some(_) => println!("{troop}: can't be bought in {edition}"),
+            None => return Err(AppError::Overflow(format!("The cost of {total} {troop}s overflowed"))),
+        }
+    }
+
+    Ok(())
+}
+
+fn show_list(category: Option<Category>, edition: Edition, aliases: bool, sort: ListOrder) {
+    let categories = match category {
+        Some(category) => vec![category],
+        None => Category::value_variants().to_vec(),
+    };
+
+    match sort {
+        ListOrder::Category => {
+            for category in categories {
+                println!("{}:", category.branch());
+
+                for troop in Troops::value_variants()
+                    .iter()
+                    .filter(|troop| troop.category() == category && troop.available_in(edition))
+                {
+                    print_list_entry(*troop, edition, aliases);
+                }
+            }
+        }
+        ListOrder::Efficiency => {
+            let mut troops: Vec<Troops> = Troops::value_variants()
+                .iter()
+                .copied()
+                .filter(|troop| categories.contains(&troop.category()) && troop.available_in(edition))
+                .collect();
+
+            // Troops that can't attack or defend sort last
+            let key = |cost: Option<f64>| cost.unwrap_or(f64::INFINITY);
+            troops.sort_by(|a, b| {
+                key(a.cost_per_attack_in(edition))
+                    .total_cmp(&key(b.cost_per_attack_in(edition)))
+                    .then(key(a.cost_per_defense_in(edition)).total_cmp(&key(b.cost_per_defense_in(edition))))
+            });
+
+            println!("By cost per attack:");
+            for troop in troops {
+                print_list_entry(troop, edition, aliases);
+            }
+        }
+    }
+}
+
+fn print_list_entry(troop: Troops, edition: Edition, aliases: bool) {
+    let currency = currency();
+    println!(
+        "\t{} : {} {currency} ({}), {} per attack, {} per defense",
+        troop,
+        troop.cost_in(edition),
+        troop.category(),
+        format_efficiency(troop.cost_per_attack_in(edition)),
+        format_efficiency(troop.cost_per_defense_in(edition))
+    );
+
+    if aliases {
+        if let Some(value) = troop.to_possible_value() {
+            println!("\t\taccepts: {}", value.get_name_and_aliases().collect::<Vec<_>>().join(", "));
+        }
+    }
+}
+
+fn show_affordable(file: &Path, nation: Nation, category: Option<Category>, json: bool) -> Result<()> {
+    let currency = currency();
+    let state = load(file, nation)?;
+    let remaining_ipc = state.get_remaining_ipc().ok_or_else(overflow)?;
+    let affordable = state.affordable(category).ok_or_else(overflow)?;
+
+    if json {
+        let affordable = serde_json::to_string_pretty(&affordable).map_err(AppError::format("serialize affordable troops"))?;
+        println!("{affordable}");
+        return Ok(());
+    }
+
+    println!("Remaining {currency}: {remaining_ipc}");
+    let mut affordable: Vec<(Troops, i32)> = affordable.into_iter().filter(|(_, ammount)| *ammount > 0).collect();
+    affordable.sort_by_key(|(troop, _)| std::cmp::Reverse(state.get_cost(*troop)));
+
+    for (troop, ammount) in affordable {
+        println!("\t{} : {} more á {} {currency}", troop, ammount, state.get_cost(troop));
+    }
+
+    Ok(())
+}
+
+fn show_top(file: &Path, nation: Nation, limit: Option<usize>) -> Result<()> {
+    let currency = currency();
+    let state = load(file, nation)?;
+    let total = state.get_total_cost().ok_or_else(overflow)?;
+
+    let mut purchases = state
+        .purchases
+        .iter()
+        .filter(|(_, ammount)| **ammount > 0)
+        .map(|(troop, ammount)| state.get_subtotal(*troop, *ammount).map(|subtotal| (*troop, *ammount, subtotal)))
+        .collect::<Option<Vec<(Troops, i32, IPC)>>>()
+        .ok_or_else(overflow)?;
+
+    if purchases.is_empty() || total <= 0 {
+        println!("No purchases have been made this round");
+        return Ok(());
+    }
+
+    // The sort is stable, so purchases costing the same stay in the order of the troops
+    purchases.sort_by_key(|(_, _, subtotal)| std::cmp::Reverse(*subtotal));
+    purchases.truncate(limit.unwrap_or(purchases.len()));
+
+    let mut table = Table::new()
+        .styled_column("Troop", Align::Left, Style::new().bold())
+        .column("Amount", Align::Right)
+        .styled_column("Subtotal", Align::Right, Style::new().yellow())
+        .column("Share", Align::Right);
+
+    for (troop, ammount, subtotal) in purchases {
+        table.add_row(vec![
+            troop.to_string(),
+            ammount.to_string(),
+            subtotal.to_string(),
+            format!("{:.1}%", 100.0 * subtotal as f64 / total as f64),
+        ]);
+    }
+
+    print!("{}", table.render(false));
+    println!("Total spent this round: {total} {currency}");
+
+    Ok(())
+}
+
+fn suggest_purchases(file: &Path, nation: Nation, category: Option<Category>) -> Result<()> {
+    let currency = currency();
+    let state = load(file, nation)?;
+    let remaining_ipc = state.get_remaining_ipc().ok_or_else(overflow)?;
+
+    if remaining_ipc <= 0 {
+        println!("There is no {currency} left to spend");
+        return Ok(());
+    }
+    if remaining_ipc > MAX_OPTIMIZED_IPC {
+        return Err(AppError::Refused(format!(
+            "Can only suggest purchases for up to {MAX_OPTIMIZED_IPC} {currency}, you have {remaining_ipc}"
+        )));
+    }
+
+    let troops: Vec<Troops> = Troops::value_variants()
+        .iter()
+        .copied()
+        .filter(|troop| troop.available_in(state.edition))
+        .filter(|troop| category.is_none_or(|category| troop.category() == category))
+        .collect();
+
+    let suggestion = fill_budget(&troops, &state, remaining_ipc);
+    let spent: IPC = suggestion.iter().map(|(troop, ammount)| state.get_cost(*troop) * ammount).sum();
+
+    if suggestion.is_empty() {
+        println!("Nothing more can be afforded with {remaining_ipc} {currency}");
+        return Ok(());
+    }
+
+    println!("Suggested additions:");
+    for (troop, ammount) in suggestion.iter() {
+        println!("\t{} : {} á {} {currency}", troop, ammount, state.get_cost(*troop));
+    }
+    println!("Spends {spent} {currency}, leaving {} {currency}", remaining_ipc - spent);
+
+    Ok(())
+}
+
+/// The troops that spend as much of `budget` as possible, using as few troops as possible to do so
+///
+/// Every troop added has to fit the caps of the state, the same as with buy-max.
+fn fill_budget(troops: &[Troops], state: &GameState, budget: IPC) -> Vec<(Troops, i32)> {
+    let budget = budget.max(0) as usize;
+    // For every amount that can be spent exactly, the fewest troops to do it along with the troops themselves
+    let mut best: Vec<Option<(i32, BTreeMap<Troops, i32>)>> = vec![None; budget + 1];
+    best[0] = Some((0, BTreeMap::new()));
+
+    for spent in 1..=budget {
+        for troop in troops {
+            let cost = state.get_cost(*troop) as usize;
+            if cost == 0 || cost > spent {
+                continue;
+            }
+            let Some((count, bought)) = best[spent - cost].as_ref() else {
+                continue;
+            };
+            if state.cap_room(*troop, bought).is_some_and(|room| room <= 0) {
+                continue;
+            }
+
+            let count = count + 1;
+            if best[spent].as_ref().is_none_or(|(fewest, _)| count < *fewest) {
+                let mut bought = bought.clone();
+                *bought.entry(*troop).or_insert(0) += 1;
+                best[spent] = Some((count, bought));
+            }
+        }
+    }
+
+    best.into_iter()
+        .rev()
+        .find_map(|spent| spent)
+        .map(|(_, bought)| bought.into_iter().collect())
+        .unwrap_or_default()
+}
+
+fn buy_max(file: &Path, nation: Nation, troop: Troops, strict: bool) -> Result<GameState> {
+    let currency = currency();
+    let state = load(file, nation)?;
+    all_available(&[(troop, 1)], state.edition)?;
+    let remaining_ipc = state.get_remaining_ipc().ok_or_else(overflow)?;
+
+    let affordable = remaining_ipc.max(0) / state.get_cost(troop);
+    if affordable == 0 {
+        return Err(AppError::Refused(format!("No more {troop}s can be bought with {remaining_ipc} {currency} left")));
+    }
+
+    let ammount = state.cap_room(troop, &BTreeMap::new()).map_or(affordable, |room| affordable.min(room));
+
+    if ammount <= 0 {
+        return Err(AppError::Refused(format!("No more {troop}s can be bought this round, the cap has been reached")));
+    }
+
+    add_purchase(file, nation, &[(troop, ammount)], None, strict)
+}
+
+/// Adds all of `purchases` to the state, or none of them if they are refused
+///
+/// If `cap` is given it is stored as the new limit for that troop type before the purchase is checked.
+fn add_purchase(
+    file: &Path,
+    nation: Nation,
+    purchases: &[(Troops, i32)],
+    cap: Option<(Troops, i32)>,
+    strict: bool,
+) -> Result<GameState> {
+    let currency = currency();
+    if let Some((troop, _)) = purchases.iter().find(|(_, ammount)| *ammount <= 0) {
+        return Err(AppError::Refused(format!(
+            "You can only purchase a positive amount of {troop}s, use remove to reduce a purchase"
+        )));
+    }
+
+    let mut state = load(file, nation)?;
+    all_available(purchases, state.edition)?;
+
+    if let Some((troop, max)) = cap {
+        state.caps.insert(troop, max);
+        progress!("At most {max} {troop}s can be bought each round");
+    }
+
+    for (troop, ammount) in purchases {
+        let Some(total) = state.purchases.get(troop).unwrap_or(&0).checked_add(*ammount) else {
+            return Err(AppError::Overflow(format!("Can't purchase that many {troop}s")));
+        };
+        state.purchases.insert(*troop, total);
+        state.actions.push(Action { troop: *troop, delta: *ammount });
+
+        if let Some(max) = state.caps.get(troop).filter(|max| total > **max) {
+            if strict {
+                return Err(AppError::Refused(format!(
+                    "Can't purchase {total} {troop}s, at most {max} can be bought each round"
+                )));
+            }
+
+            println!("WARNING: you are buying {total} {troop}s, but at most {max} can be bought each round!");
+        }
+    }
+
+    let remaining_ipc = state.get_remaining_ipc().ok_or_else(overflow)?;
+
+    if remaining_ipc < 0 {
+        if strict {
+            return Err(AppError::Refused(format!(
+                "Can't make this purchase, it would exceed your {currency} by {}",
+                -remaining_ipc
+            )));
+        }
+
+        println!("WARNING: your purchases exceed your {currency} by {}!", -remaining_ipc);
+    }
+
+    for (troop, ammount) in purchases {
+        progress!("Added a purchase of {} {}s for {}", ammount, troop, state.get_cost(*troop) * ammount);
+    }
+
+    if purchases.len() > 1 {
+        progress!("Total cost of purchases: {} {currency}", state.ipc - remaining_ipc);
+    }
+
+    progress!("Remaining {currency}: {remaining_ipc}");
+    Ok(state)
+}
+
+/// Checks that every troop can be bought in the edition
+fn all_available(purchases: &[(Troops, i32)], edition: Edition) -> Result<()> {
+    match purchases.iter().find(|(troop, _)| !troop.available_in(edition)) {
+        Some((troop, _)) => Err(AppError::UnavailableTroop { troop: *troop, edition }),
+        None => Ok(()),
+    }
+}
+
+fn show_whatif(file: &Path, nation: Nation, purchases: &[(Troops, i32)]) -> Result<()> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    all_available(purchases, state.edition)?;
+    let current_cost = state.get_total_cost().ok_or_else(overflow)?;
+
+    println!("Hypothetically buying:");
+    for (troop, ammount) in purchases {
+        let Some(total) = state.purchases.get(troop).unwrap_or(&0).checked_add(*ammount) else {
+            return Err(AppError::Overflow(format!("Can't purchase that many {troop}s")));
+        };
+        state.purchases.insert(*troop, total);
+
+        match state.get_subtotal(*troop, *ammount) {
+            Some(cost) => println!("\t{ammount} {troop}s for {cost} {currency}"),
+            None => println!("\t{ammount} {troop}s for more {currency} than can be counted"),
+        }
+    }
+
+    let (Some(cost), Some(remaining_ipc)) = (state.get_total_cost(), state.get_remaining_ipc()) else {
+        return Err(overflow());
+    };
+
+    println!("Current purchases: {current_cost} {currency}");
+    println!("Combined cost: {cost} {currency}");
+    println!("Remaining {currency} if bought: {remaining_ipc}");
+
+    if remaining_ipc < 0 {
+        println!("WARNING: these purchases would exceed your {currency} by {}!", -remaining_ipc);
+    }
+
+    Ok(())
+}
+
+/// Removes `ammount` troops from the purchase, or all of them if it is `None`
+fn remove_purchase(file: &Path, nation: Nation, troop: Troops, ammount: Option<i32>) -> Result<GameState> {
+    let mut state = load(file, nation)?;
+    let before = state.purchases.get(&troop).copied().unwrap_or(0);
+    match ammount {
+        Some(ammount) => {
+            state.purchases.insert(troop, state.purchases.get(&troop).unwrap_or(&0).saturating_sub(ammount));
+            progress!("Removing {ammount} {troop}s from purchase")
+        }
+        None => {
+            state.purchases.insert(troop, 0);
+            progress!("Removing all {troop}s from purchase")
+        }
+    };
+
+    let delta = state.purchases[&troop].max(0) - before;
+    if delta != 0 {
+        state.actions.push(Action { troop, delta });
+    }
+
+    if state.purchases[&troop] <= 0 {
+        state.purchases.remove(&troop);
+    }
+
+    Ok(state)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn commit_purchase(
+    file: &Path,
+    nation: Nation,
+    new_ipc: Option<IPC>,
+    delta: Option<IPC>,
+    sources: Vec<IncomeSource>,
+    bonus: IPC,
+    skip_build: bool,
+    yes: bool,
+) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    if let Some(delta) = delta {
+        let Some(base_income) = state.base_income else {
+            return Err(AppError::Refused("There is no income to change, set it with set-income first".to_string()));
+        };
+        let Some(changed) = base_income.checked_add(delta).filter(|income| *income >= 0) else {
+            return Err(AppError::Refused(format!(
+                "Can't change the income of {base_income} {currency} by {delta:+}, it can't be negative"
+            )));
+        };
+
+        info!("Income of {nation} changed from {base_income} to {changed}");
+        progress!("Income changed from {base_income} {currency} to {changed} {currency} each round");
+        state.base_income = Some(changed);
+    }
+
+    // Sources stand in for the income when it isn't given, and have to agree with it when it is
+    let new_ipc = if sources.is_empty() {
+        new_ipc
+    } else {
+        let total = sources
+            .iter()
+            .try_fold(0, |acc: IPC, source| acc.checked_add(source.ipc))
+            .ok_or_else(|| AppError::Overflow("The income of the sources overflowed".to_string()))?;
+
+        match new_ipc {
+            Some(new_ipc) if new_ipc != total => {
+                return Err(AppError::Refused(format!(
+                    "The sources add up to {total} {currency}, but the income given is {new_ipc} {currency}"
+                )));
+            }
+            _ => Some(total),
+        }
+    };
+
+    let new_ipc = match (new_ipc, state.base_income) {
+        (Some(new_ipc), Some(base_income)) if new_ipc != base_income => {
+            progress!("Income this round is {new_ipc} {currency} instead of the usual {base_income} {currency}");
+            new_ipc
+        }
+        (Some(new_ipc), _) => new_ipc,
+        (None, Some(base_income)) => base_income,
+        (None, None) => {
+            return Err(AppError::Refused(
+                "No income was given, pass it to commit or set the usual income with set-income".to_string(),
+            ));
+        }
+    };
+
+    if !skip_build && state.purchases.values().all(|ammount| *ammount == 0) {
+        return Err(AppError::Refused(
+            "Nothing has been purchased this round, use --skip-build to commit anyway".to_string(),
+        ));
+    }
+
+    let violations = state.category_cap_violations();
+    if !violations.is_empty() {
+        let violations: Vec<String> = violations.iter().map(|violation| format!("\t{violation}")).collect();
+        return Err(AppError::Refused(format!("Can't commit these purchases:\n{}", violations.join("\n"))));
+    }
+
+    let (Some(cost), Some(remaining_ipc)) = (state.get_total_cost(), state.get_remaining_ipc()) else {
+        return Err(overflow());
+    };
+
+    if remaining_ipc >= 0 {
+        let Some(new_total) = remaining_ipc.checked_add(new_ipc).and_then(|ipc| ipc.checked_add(bonus)) else {
+            return Err(AppError::Overflow(format!("The new {currency} total overflowed")));
+        };
+        let Some(total_spent) = state.total_spent.checked_add(cost) else {
+            return Err(AppError::Overflow("The total spent this game overflowed".to_string()));
+        };
+
+        let count: i32 = state.purchases.values().sum();
+        if !yes && !confirm_on_stdin(&format!("Commit {count} purchases for {cost} {currency}?")) {
+            return Err(AppError::Declined("Nothing was committed".to_string()));
+        }
+
+        let mut units = state.units.clone();
+        for (troop, ammount) in state.purchases.iter() {
+            let Some(total) = units.get(troop).unwrap_or(&0).checked_add(*ammount) else {
+                return Err(AppError::Overflow(format!("Can't have that many {troop}s on the board")));
+            };
+            units.insert(*troop, total);
+        }
+
+        progress!("commiting purchases...");
+        if !state.purchases.is_empty() {
+            let receipt = state.purchase_table(PurchaseOrder::default()).render(false);
+            progress!("{receipt}Total cost {cost} {currency}");
+        }
+        state.ipc = new_total;
+        state.total_spent = total_spent;
+        state.units = units;
+        state.history.push(RoundRecord {
+            round: state.round,
+            purchases: std::mem::take(&mut state.purchases),
+            cost,
+            ipc: state.ipc,
+            income: new_ipc,
+            bonus,
+            expenses: std::mem::take(&mut state.expenses),
+            committed_at: Some(Utc::now()),
+            sources,
+        });
+        state.actions.clear();
+
+        progress!("{currency} remaining {remaining_ipc}");
+        if bonus > 0 {
+            progress!("National objective income {bonus}");
+        }
+        progress!("New {currency} total {}", state.ipc);
+
+        state.round += 1;
+        progress!("Starting round {}", state.round);
+
+        Ok(state)
+    } else {
+        Err(AppError::Refused(format!("You don't have enough {currency} to pay for your purchases")))
+    }
+}
+
+/// The combined economy of one side of the war
+#[derive(Default)]
+struct AllianceTotals {
+    nations: Vec<Nation>,
+    ipc: IPC,
+    last_income: IPC,
+    total_income: IPC,
+    pending: IPC,
+}
+
+impl AllianceTotals {
+    /// Returns `None` if any of the totals overflow
+    fn add(&mut self, nation: Nation, state: &GameState) -> Option<()> {
+        self.nations.push(nation);
+        self.ipc = self.ipc.checked_add(state.ipc)?;
+        self.pending = self.pending.checked_add(state.get_total_cost()?)?;
+
+        if let Some(last) = state.history.last() {
+            self.last_income = self.last_income.checked_add(last.income)?.checked_add(last.bonus)?;
+        }
+
+        for record in state.history.iter() {
+            self.total_income = self.total_income.checked_add(record.income)?.checked_add(record.bonus)?;
+        }
+
+        Some(())
+    }
+}
+
+fn show_totals(file: &Path, axis: &[Nation], allies: &[Nation]) -> Result<()> {
+    let game = load_game(file)?;
+
+    let mut axis_totals = AllianceTotals::default();
+    let mut allies_totals = AllianceTotals::default();
+
+    for (nation, state) in game.nations.iter() {
+        let alliance = if axis.contains(nation) {
+            Alliance::Axis
+        } else if allies.contains(nation) {
+            Alliance::Allies
+        } else {
+            nation.alliance()
+        };
+
+        let totals = match alliance {
+            Alliance::Axis => &mut axis_totals,
+            Alliance::Allies => &mut allies_totals,
+        };
+
+        if totals.add(*nation, state).is_none() {
+            return Err(overflow());
+        }
+    }
+
+    let names = |totals: &AllianceTotals| {
+        totals.nations.iter().map(Nation::to_string).collect::<Vec<_>>().join(", ")
+    };
+
+    let mut table = Table::new()
+        .column("", Align::Left)
+        .column("Axis", Align::Right)
+        .column("Allies", Align::Right);
+
+    table.add_row(vec!["Nations".to_string(), names(&axis_totals), names(&allies_totals)]);
+    for (label, axis, allies) in [
+        (currency(), axis_totals.ipc, allies_totals.ipc),
+        ("Income last round", axis_totals.last_income, allies_totals.last_income),
+        ("Total income", axis_totals.total_income, allies_totals.total_income),
+        ("Pending purchases", axis_totals.pending, allies_totals.pending),
+    ] {
+        table.add_row(vec![label.to_string(), axis.to_string(), allies.to_string()]);
+    }
+
+    print!("{}", table.render(false));
+
+    Ok(())
+}
+
+/// Simulates the battle and prints the report, `techs` are those of the attackers and then the defenders
+fn simulate_battle(
+    attackers: &[(Troops, i32)],
+    defenders: &[(Troops, i32)],
+    techs: [&[Tech]; 2],
+    iterations: u32,
+    seed: Option<u64>,
+    detailed: bool,
+    rules: &Rules,
+) -> Result<()> {
+    if iterations == 0 {
+        return Err(AppError::Refused("Need at least one iteration to simulate a battle".to_string()));
+    }
+
+    let mut rng = seeded_rng(seed);
+    let [attacker_techs, defender_techs] = techs;
+    let report = battle::simulate(attackers, defenders, attacker_techs, defender_techs, iterations, rules, &mut rng);
+    println!("{report}");
+
+    if detailed {
+        print!("{}", report.distribution());
+    }
+
+    Ok(())
+}
+
+fn roll_dice(dice: u32, hit_on: u8, seed: Option<u64>) {
+    let mut rng = seeded_rng(seed);
+    let rolls: Vec<u8> = (0..dice).map(|_| dice::roll(&mut rng)).collect();
+    let hits = rolls.iter().filter(|roll| **roll <= hit_on).count();
+
+    let rolls: Vec<String> = rolls.iter().map(u8::to_string).collect();
+    println!("Rolled: {}", rolls.join(", "));
+    println!("{hits} hits");
+}
+
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+fn update_config(mut config: Config, action: ConfigAction) -> Result<()> {
+    match action {
+        ConfigAction::Show => print!("{config}"),
+        ConfigAction::Set { setting } => {
+            match setting {
+                Setting::Edition { edition } => {
+                    config.edition = Some(edition);
+                    progress!("Default edition set to {edition}");
+                }
+                Setting::Currency { label } => {
+                    progress!("Currency set to {label}");
+                    config.currency = Some(label);
+                }
+                Setting::Lang { lang } => {
+                    progress!("Troop names are shown in {lang}");
+                    config.lang = Some(lang);
+                }
+                Setting::Compact { enabled } => {
+                    progress!("Compact saves {}", if enabled { "enabled" } else { "disabled" });
+                    config.compact = Some(enabled);
+                }
+                Setting::Indent { width } => {
+                    progress!("Saved games are indented by {width} spaces");
+                    config.indent = Some(width);
+                }
+                Setting::StructNames { enabled } => {
+                    progress!("Struct names in saves {}", if enabled { "enabled" } else { "disabled" });
+                    config.struct_names = Some(enabled);
+                }
+                Setting::PurgeZeros { enabled } => {
+                    progress!("Purging empty purchases on load {}", if enabled { "enabled" } else { "disabled" });
+                    config.purge_zeros = Some(enabled);
+                }
+            }
+
+            config.save()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn convert(file: &Path, nation: Nation, edition: Edition, reprice: bool, strict: bool) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    if state.edition == edition {
+        return Err(AppError::Refused(format!("The game already is in {edition}")));
+    }
+
+    // The troops are kept as they are, so the purchases must all exist in the new edition
+    let purchases: Vec<(Troops, i32)> = state.purchases.iter().map(|(troop, ammount)| (*troop, *ammount)).collect();
+    all_available(&purchases, edition)?;
+
+    let old_edition = state.edition;
+    let old_cost = state.get_total_cost().ok_or_else(overflow)?;
+    state.edition = edition;
+    let (Some(new_cost), Some(remaining_ipc)) = (state.get_total_cost(), state.get_remaining_ipc()) else {
+        return Err(overflow());
+    };
+
+    if reprice {
+        println!("Purchases cost {old_cost} {currency} in {old_edition} and {new_cost} {currency} in {edition} ({:+})", new_cost - old_cost);
+
+        if remaining_ipc < 0 {
+            if strict {
+                return Err(AppError::Refused(format!(
+                    "Can't convert the game, the purchases would exceed your {currency} by {}",
+                    -remaining_ipc
+                )));
+            }
+
+            println!("WARNING: your purchases now exceed your {currency} by {}!", -remaining_ipc);
+        }
+    }
+
+    progress!("Converted the game from {old_edition} to {edition}");
+    Ok(state)
+}
+
+fn purge_zeros(file: &Path, nation: Nation) -> Result<GameState> {
+    let mut state = load(file, nation)?;
+
+    match state.purge_zeros() {
+        0 => progress!("There were no empty purchases"),
+        1 => progress!("Removed 1 empty purchase"),
+        purged => progress!("Removed {purged} empty purchases"),
+    }
+
+    Ok(state)
+}
+
+fn show_army(file: &Path, nation: Nation) -> Result<()> {
+    let currency = currency();
+    let state = load(file, nation)?;
+
+    if state.units.is_empty() {
+        println!("No units are on the board, use army --add to record them");
+        return Ok(());
+    }
+
+    for (troop, ammount) in state.units.iter() {
+        match state.get_subtotal(*troop, *ammount) {
+            Some(value) => println!("\t{troop} : {ammount} worth {value} {currency}"),
+            None => println!("\t{troop} : {ammount} worth more {currency} than can be counted"),
+        }
+    }
+
+    let value = state.get_army_value().ok_or_else(overflow)?;
+    println!("Total unit value: {value} {currency}");
+
+    Ok(())
+}
+
+/// Adds the units in `add` to the board and takes the units in `remove` off it
+fn update_army(file: &Path, nation: Nation, add: &[(Troops, i32)], remove: &[(Troops, i32)]) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+
+    for (troop, ammount) in add {
+        let Some(total) = state.units.get(troop).unwrap_or(&0).checked_add(*ammount) else {
+            return Err(AppError::Overflow(format!("Can't have that many {troop}s on the board")));
+        };
+        state.units.insert(*troop, total);
+        progress!("Added {ammount} {troop}s to the board");
+    }
+
+    for (troop, ammount) in remove {
+        take_units(&mut state, *troop, *ammount, false)?;
+        progress!("Removed {ammount} {troop}s from the board");
+    }
+
+    let value = state.get_army_value().ok_or_else(overflow)?;
+    progress!("Total unit value: {value} {currency}");
+    Ok(state)
+}
+
+fn lose_units(file: &Path, nation: Nation, troop: Troops, ammount: i32, clamp: bool) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+
+    let lost = take_units(&mut state, troop, ammount, clamp)?;
+    let value = state.get_subtotal(troop, lost).ok_or_else(overflow)?;
+    progress!("Lost {lost} {troop}s worth {value} {currency}");
+
+    let value = state.get_army_value().ok_or_else(overflow)?;
+    progress!("Total unit value: {value} {currency}");
+    Ok(state)
+}
+
+/// Takes `ammount` troops off the board and returns how many were taken
+///
+/// Taking more than are on the board fails, unless `clamp` is set in which case all of them are taken.
+fn take_units(state: &mut GameState, troop: Troops, ammount: i32, clamp: bool) -> Result<i32> {
+    let owned = state.units.get(&troop).copied().unwrap_or(0);
+    if ammount > owned && !clamp {
+        return Err(AppError::Refused(format!(
+            "Can't remove {ammount} {troop}s, only {owned} are on the board"
+        )));
+    }
+
+    let taken = ammount.min(owned);
+    if taken == owned {
+        state.units.remove(&troop);
+    } else {
+        state.units.insert(troop, owned - taken);
+    }
+
+    Ok(taken)
+}
+
+fn show_category_caps(file: &Path, nation: Nation) -> Result<()> {
+    let state = load(file, nation)?;
+
+    if state.category_caps.is_empty() {
+        println!("No categories are limited");
+    }
+
+    for category in Category::value_variants() {
+        if let Some(max) = state.category_caps.get(category) {
+            println!("{category}: at most {max} units each round");
+        }
+    }
+
+    Ok(())
+}
+
+fn set_category_caps(file: &Path, nation: Nation, caps: &[(Category, i32)], clear: bool) -> Result<GameState> {
+    if let Some((category, max)) = caps.iter().find(|(_, max)| *max < 0) {
+        return Err(AppError::Refused(format!("Can't limit {category} units to {max}, the limit can't be negative")));
+    }
+
+    let mut state = load(file, nation)?;
+    if clear {
+        state.category_caps.clear();
+        progress!("Removed all category limits");
+    }
+
+    for (category, max) in caps {
+        state.category_caps.insert(*category, *max);
+        progress!("At most {max} {category} units can be committed each round");
+    }
+
+    Ok(state)
+}
+
+fn set_income(file: &Path, nation: Nation, ipc: IPC) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    match state.base_income.replace(ipc) {
+        Some(previous) => {
+            info!("Income of {nation} changed from {previous} to {ipc}");
+            progress!("Income changed from {previous} {currency} to {ipc} {currency} each round");
+        }
+        None => progress!("Income set to {ipc} {currency} each round"),
+    }
+
+    Ok(state)
+}
+
+fn spend(file: &Path, nation: Nation, expense: Expense) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    if expense.ipc > state.ipc {
+        return Err(AppError::InsufficientIpc {
+            needed: expense.ipc,
+            available: state.ipc,
+        });
+    }
+
+    state.ipc -= expense.ipc;
+    progress!("Spent {expense}");
+    state.expenses.push(expense);
+
+    if state.get_remaining_ipc().is_some_and(|remaining_ipc| remaining_ipc < 0) {
+        println!("WARNING: you can no longer afford your purchases!");
+    }
+
+    progress!("{currency} left: {}", state.ipc);
+    Ok(state)
+}
+
+fn repair(file: &Path, nation: Nation, troop: Troops, ipc: IPC) -> Result<GameState> {
+    if !troop.repairable() {
+        return Err(AppError::Refused(format!(
+            "{troop}s can't be repaired, only Battleships, Aircraft Carriers and Industrial Complexes can"
+        )));
+    }
+
+    spend(file, nation, Expense { ipc, reason: None, repaired: Some(troop) })
+}
+
+fn bank(file: &Path, nation: Nation, ipc: IPC) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    if ipc > state.ipc {
+        return Err(AppError::Refused(format!("Can't bank {ipc} {currency}, you only have {}", state.ipc)));
+    }
+    let Some(banked) = state.banked.checked_add(ipc) else {
+        return Err(AppError::Overflow(format!("Can't bank that much {currency}")));
+    };
+
+    state.ipc -= ipc;
+    state.banked = banked;
+    progress!("Banked {ipc} {currency}, {} {currency} banked in total", state.banked);
+
+    if state.get_remaining_ipc().is_some_and(|remaining_ipc| remaining_ipc < 0) {
+        println!("WARNING: you can no longer afford your purchases!");
+    }
+
+    progress!("{currency} left: {}", state.ipc);
+    Ok(state)
+}
+
+fn unbank(file: &Path, nation: Nation, ipc: IPC) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    if ipc > state.banked {
+        return Err(AppError::Refused(format!(
+            "Can't unbank {ipc} {currency}, only {} {currency} is banked",
+            state.banked
+        )));
+    }
+    let Some(total) = state.ipc.checked_add(ipc) else {
+        return Err(AppError::Overflow(format!("The new {currency} total overflowed")));
+    };
+
+    state.banked -= ipc;
+    state.ipc = total;
+    progress!("Unbanked {ipc} {currency}, {} {currency} is still banked", state.banked);
+    progress!("{currency} left: {}", state.ipc);
+    Ok(state)
+}
+
+/// Buys `tokens` research tokens, then rolls all of them if `roll` is set
+fn research(file: &Path, nation: Nation, tokens: u32, roll: bool, seed: Option<u64>) -> Result<GameState> {
+    let currency = currency();
+    if tokens == 0 && !roll {
+        return Err(AppError::Refused(
+            "Give the number of --tokens to buy, or --roll to roll the tokens you have".to_string(),
+        ));
+    }
+
+    let mut state = load(file, nation)?;
+    if tokens > 0 {
+        let token_cost = state.edition.tech_chart().token_cost();
+        let Some(ipc) = IPC::try_from(tokens).ok().and_then(|tokens| tokens.checked_mul(token_cost)) else {
+            return Err(AppError::Overflow("Can't buy that many research tokens".to_string()));
+        };
+        if ipc > state.ipc {
+            return Err(AppError::Refused(format!(
+                "Can't buy {tokens} research tokens for {ipc} {currency}, you only have {}",
+                state.ipc
+            )));
+        }
+
+        state.ipc -= ipc;
+        state.tech_tokens = state.tech_tokens.saturating_add(tokens);
+        let expense = Expense {
+            ipc,
+            reason: Some(format!("{tokens} research tokens")),
+            repaired: None,
+        };
+        progress!("Spent {expense}");
+        state.expenses.push(expense);
+
+        if state.get_remaining_ipc().is_some_and(|remaining_ipc| remaining_ipc < 0) {
+            println!("WARNING: you can no longer afford your purchases!");
+        }
+    }
+
+    if roll {
+        if state.tech_tokens == 0 {
+            return Err(AppError::Refused("You have no research tokens to roll".to_string()));
+        }
+
+        let chart = state.edition.tech_chart();
+        progress!("Rolling on the {chart} tech chart");
+        let research = tech::research(state.tech_tokens, &state.techs, chart, &mut seeded_rng(seed));
+        let rolls: Vec<String> = research.rolls.iter().map(u8::to_string).collect();
+        println!("Rolled: {}", rolls.join(", "));
+
+        if research.rolls.contains(&6) {
+            // All tokens are used up by a breakthrough
+            state.tech_tokens = 0;
+
+            if research.breakthroughs.is_empty() {
+                println!("Breakthrough, but every technology has already been researched");
+            }
+            for tech in research.breakthroughs {
+                println!("Breakthrough! Researched {tech}");
+                state.techs.push(tech);
+            }
+        } else {
+            println!("No breakthrough, keeping {} research tokens", state.tech_tokens);
+        }
+    }
+
+    progress!("{currency} left: {}", state.ipc);
+    Ok(state)
+}
+
+fn reset_purchases(file: &Path, nation: Nation, yes: bool) -> Result<GameState> {
+    let currency = currency();
+    let mut state = load(file, nation)?;
+    let cost = state.get_total_cost().map_or_else(|| "overflowed".to_string(), |cost| cost.to_string());
+
+    if !yes && !confirm_on_stdin(&format!("Discard all purchases worth {cost} {currency}?")) {
+        return Err(AppError::Declined("Keeping purchases".to_string()));
+    }
+
+    state.purchases.clear();
+    state.actions.clear();
+    progress!("Discarded purchases worth {cost} {currency}");
+    Ok(state)
+}
+
+fn save_plan(file: &Path, nation: Nation, name: &str) -> Result<()> {
+    let state = load(file, nation)?;
+
+    let purchases: BTreeMap<Troops, i32> = state.purchases.into_iter().filter(|(_, ammount)| *ammount > 0).collect();
+    if purchases.is_empty() {
+        return Err(AppError::Refused("There are no purchases to save as a plan".to_string()));
+    }
+
+    (Plan { purchases }).save(name)?;
+    progress!("Saved the purchases as the plan {name}");
+    Ok(())
+}
+
+/// Adds the purchases of a plan, checked against the budget like any other purchase
+fn load_plan(file: &Path, nation: Nation, name: &str, strict: bool) -> Result<GameState> {
+    let plan = Plan::load(name)?;
+
+    let purchases: Vec<(Troops, i32)> = plan.purchases.into_iter().filter(|(_, ammount)| *ammount > 0).collect();
+
+    if purchases.is_empty() {
+        return Err(AppError::Refused(format!("The plan {name} has no purchases")));
+    }
+
+    add_purchase(file, nation, &purchases, None, strict)
+}
+
+fn show_plans() {
+    let names = plan::list();
+
+    if names.is_empty() {
+        println!("There are no saved plans, use plan save to add one");
+        return;
+    }
+
+    println!("Saved plans:");
+    for name in names {
+        println!("\t{name}");
+    }
+}
+
+/// Reads commands from a prompt and runs each of them as if given on the command line
+fn interactive(options: &Options) {
+    progress!("Enter a command, help to list them or quit to end the session");
+
+    loop {
+        print!("> ");
+        if std::io::stdout().flush().is_err() {
+            return;
+        }
+
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to read command due to error {e:?}");
+                return;
+            }
+        }
+
+        if matches!(line.trim(), "quit" | "exit") {
+            return;
+        }
+
+        match parse_line(&line) {
+            Some(Ok(Commands::Interactive)) => println!("Already in an interactive session"),
+            Some(Ok(command)) => {
+                if let Err(e) = run(options, command) {
+                    report(&e);
+                }
+            }
+            Some(Err(e)) => {
+                let _ = e.print();
+            }
+            None => {}
+        }
+    }
+}
+
+/// Runs every command in the file, saving the games they changed only once all of them succeeded
+///
+/// Nothing is saved if a command fails, so an undo afterwards reverts the whole batch.
+fn run_batch(options: &Options, path: &Path) -> Result<()> {
+    let contents = fs::read_to_string(path).map_err(AppError::io(format!("open {}", path.display())))?;
+
+    *BATCH_GAMES.lock().unwrap_or_else(PoisonError::into_inner) = Some(BTreeMap::new());
+    let result = run_batch_lines(options, path, &contents);
+    let games = BATCH_GAMES.lock().unwrap_or_else(PoisonError::into_inner).take().unwrap_or_default();
+
+    if result.is_err() && !games.is_empty() {
+        println!("Nothing was saved since the batch didn't finish");
+    }
+    result?;
+
+    for (file, contents) in games {
+        save_contents(&file, contents)?;
+    }
+
+    Ok(())
+}
+
+fn run_batch_lines(options: &Options, path: &Path, contents: &str) -> Result<()> {
+    for (number, line) in contents.lines().enumerate().map(|(index, line)| (index + 1, line)) {
+        let command = match parse_line(line) {
+            Some(Ok(Commands::Interactive | Commands::Batch { .. })) => {
+                return Err(AppError::Refused(format!(
+                    "Line {number} of {}: interactive and batch can't be used in a batch file",
+                    path.display()
+                )));
+            }
+            // These change files of their own rather than saving a game, so they can't wait for the batch to finish
+            Some(Ok(Commands::Undo | Commands::Game { action: GameAction::New { .. } | GameAction::Delete { .. } })) => {
+                return Err(AppError::Refused(format!(
+                    "Line {number} of {}: undo, game new and game delete can't be used in a batch file",
+                    path.display()
+                )));
+            }
+            Some(Ok(command)) => command,
+            Some(Err(e)) => {
+                println!("Line {number} of {} is not a valid command:", path.display());
+                let _ = e.print();
+                return Err(AppError::Refused(format!("Stopped at line {number} of {}", path.display())));
+            }
+            None => continue,
+        };
+
+        if let Err(e) = run(options, command) {
+            report(&e);
+            return Err(AppError::Refused(format!(
+                "Stopped at line {number} of {}: {} failed",
+                path.display(),
+                line.trim()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a line the same way as the command line, `None` if it is blank or a comment
+fn parse_line(line: &str) -> Option<std::result::Result<Commands, clap::Error>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    Some(Prompt::try_parse_from(line.split_whitespace()).map(|prompt| prompt.command))
+}
+
+/// Asks a yes or no question, refusing without asking if stdin isn't a terminal so scripts never hang
+fn confirm_on_stdin(question: &str) -> bool {
+    let stdin = io::stdin();
+    let is_terminal = stdin.is_terminal();
+    confirm(question, &mut stdin.lock(), is_terminal)
+}
+
+/// Asks a yes or no question, anything but a yes read from `input` counts as a no
+fn confirm(question: &str, input: &mut impl BufRead, is_terminal: bool) -> bool {
+    if !is_terminal {
+        println!("{question} Not asking since stdin isn't a terminal, use --yes to go ahead");
+        return false;
+    }
+
+    print!("{question} [y/N] ");
+    if std::io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(e) => {
+            error!("Failed to read answer due to error {e:?}");
+            false
+        }
+    }
+}
+
+fn export(file: &Path, format: ExportFormat, out: Option<&Path>) -> Result<()> {
+    let game = load_game(file)?;
+
+    let contents = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&game)
+            .map(|json| json + "\n")
+            .map_err(AppError::format("export game state"))?,
+        ExportFormat::Ron => ron::ser::to_string_pretty(&game, PrettyConfig::default())
+            .map(|ron| ron + "\n")
+            .map_err(AppError::format("export game state"))?,
+        ExportFormat::Csv => {
+            if game.nations.values().all(|state| state.history.is_empty()) {
+                return Err(AppError::Refused(
+                    "No rounds have been committed yet, so there is no history to export".to_string(),
+                ));
+            }
+            history_csv(&game)
+        }
+    };
+
+    match out {
+        Some(out) => {
+            fs::write(out, contents).map_err(AppError::io(format!("write {}", out.display())))?;
+            progress!("Exported game state to {}", out.display());
+        }
+        None => print!("{contents}"),
+    }
+
+    Ok(())
+}
+
+/// The round history of every nation as CSV with a header row
+fn history_csv(game: &Game) -> String {
+    let mut csv = String::from("nation,round,income,bonus,total_spent,remaining,committed_at\n");
+
+    for (nation, state) in game.nations.iter() {
+        for record in state.history.iter() {
+            // The IPC left over once the round was paid for, before the income came in
+            let remaining = record.ipc as i64 - record.total_income();
+            let fields = [
+                nation.to_string(),
+                record.round.to_string(),
+                record.income.to_string(),
+                record.bonus.to_string(),
+                record.total_spent().to_string(),
+                remaining.to_string(),
+                record.committed_at.map(|date| date.to_rfc3339()).unwrap_or_default(),
+            ];
+
+            csv.push_str(&fields.map(|field| csv_field(&field)).join(","));
+            csv.push('\n');
+        }
+    }
+
+    csv
+}
+
+/// Quotes a field if it contains anything that would break the row apart
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn import(file: &Path, path: &Path, format: Format) -> Result<()> {
+    let contents = fs::read_to_string(path).map_err(AppError::io(format!("open {}", path.display())))?;
+
+    let game = match format {
+        Format::Json => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+        Format::Ron => parse_game(&contents).map_err(|e| e.to_string()),
+    }
+    .and_then(Game::migrate)
+    .map_err(AppError::format("import game state"))?;
+
+    progress!("Imported game state from {}", path.display());
+    save_game(file, &game)
+}
+
+fn undo(file: &Path) -> Result<()> {
+    if is_stdio(file) {
+        return Err(AppError::Refused("Games read from stdin have no backup to undo to".to_string()));
+    }
+
+    let backup = backup_path(file);
+
+    if !backup.exists() {
+        return Err(AppError::Refused("There is nothing to undo".to_string()));
+    }
+
+    // Copy rather than save so the backup stays in place and a second undo is a no-op
+    fs::copy(&backup, file).map_err(AppError::io(format!("restore state from {}", backup.display())))?;
+    progress!("Reverted to previous state");
+    Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// The temporary file a game is written to before it replaces the real one,
+/// kept in the same directory so the rename stays on one filesystem
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO)
+}
+
+/// The game saved to `path` earlier in the batch being run, if any
+fn batched_game(path: &Path) -> Option<String> {
+    BATCH_GAMES.lock().unwrap_or_else(PoisonError::into_inner).as_ref()?.get(path).cloned()
+}
+
+/// Reads the game from stdin the first time, and from what was saved since after that
+fn read_stdin() -> io::Result<String> {
+    let mut game = STDIO_GAME.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(contents) = game.as_ref() {
+        return Ok(contents.clone());
+    }
+
+    // Don't wait for a game to be typed in, a terminal means there is none
+    let mut contents = String::new();
+    if !io::stdin().is_terminal() {
+        io::stdin().lock().read_to_string(&mut contents)?;
+    }
+
+    *game = Some(contents.clone());
+    Ok(contents)
+}
+
+/// Whether there is a game to load, stdin counts as empty if nothing was piped to it
+fn game_exists(path: &Path) -> bool {
+    if batched_game(path).is_some() {
+        true
+    } else if is_stdio(path) {
+        read_stdin().is_ok_and(|contents| !contents.trim().is_empty())
+    } else {
+        path.exists()
+    }
+}
+
+/// Loads the state of a single nation in the game
+fn load(path: &Path, nation: Nation) -> Result<GameState> {
+    let mut game = load_game(path)?;
+    game.nations.remove(&nation).ok_or(AppError::UnknownNation(nation))
+}
+
+/// Loads the game, recovering it from the backup if the file is corrupt
+fn load_game(path: &Path) -> Result<Game> {
+    if let Some(contents) = batched_game(path) {
+        return parse_game(&contents)
+            .map(purge_on_load)
+            .map_err(|e| parse_error(path.display().to_string(), e));
+    }
+
+    if is_stdio(path) {
+        return load_stdin_game().map(purge_on_load);
+    }
+
+    let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => AppError::FileNotFound(path.to_path_buf()),
+        _ => AppError::io("load game state from file")(e),
+    })?;
+
+    let game = match parse_game(&contents) {
+        Ok(game) => game,
+        Err(e) => recover_game(path, e)?,
+    };
+
+    game.migrate()
+        .map(purge_on_load)
+        .map_err(|e| AppError::Refused(format!("Failed to load the game in {}: {e}", path.display())))
+}
+
+/// Drops the empty purchases of every nation if the config asks for it
+fn purge_on_load(mut game: Game) -> Game {
+    if PURGE_ON_LOAD.load(Ordering::Relaxed) {
+        for (nation, state) in game.nations.iter_mut() {
+            let purged = state.purge_zeros();
+            if purged > 0 {
+                debug!("Dropped {purged} empty purchases of {nation} on load");
+            }
+        }
+    }
+
+    game
+}
+
+/// Loads the game piped to stdin, there is no backup to recover it from
+fn load_stdin_game() -> Result<Game> {
+    let contents = read_stdin().map_err(AppError::io("read game state from stdin"))?;
+
+    if contents.trim().is_empty() {
+        return Err(AppError::Refused("No game was piped to stdin".to_string()));
+    }
+
+    let game = parse_game(&contents).map_err(|e| parse_error("stdin".to_string(), e))?;
+    game.migrate()
+        .map_err(|e| AppError::Refused(format!("Failed to load the game from stdin: {e}")))
+}
+
+fn parse_error(source_name: String, e: ron::error::SpannedError) -> AppError {
+    AppError::ParseError {
+        source_name,
+        line: e.position.line,
+        column: e.position.col,
+        message: e.code.to_string(),
+    }
+}
+
+/// Falls back to the backup of a game that failed to parse
+fn recover_game(path: &Path, e: ron::error::SpannedError) -> Result<Game> {
+    let backup = backup_path(path);
+    match read_game(&backup) {
+        Some(game) => {
+            println!(
+                "{} is corrupt at line {}, column {}: {}",
+                path.display(),
+                e.position.line,
+                e.position.col,
+                e.code
+            );
+            println!("Recovered the game from the backup in {}", backup.display());
+            Ok(game)
+        }
+        None => Err(parse_error(path.display().to_string(), e)),
+    }
+}
+
+/// Reads a game without any recovery or error reporting
+fn read_game(path: &Path) -> Option<Game> {
+    fs::read_to_string(path).ok().and_then(|contents| parse_game(&contents).ok())
+}
+
+/// Parses a game, falling back to the single nation format older versions saved
+fn parse_game(contents: &str) -> std::result::Result<Game, ron::error::SpannedError> {
+    ron::from_str(contents).or_else(|e| {
+        ron::from_str(contents)
+            .map(Game::from_single_nation)
+            .map_err(|_| e)
+    })
+}
+
+/// Saves the state of a single nation, keeping the rest of the game as it is
+fn save(path: &Path, nation: Nation, state: GameState) -> Result<()> {
+    let mut game = if game_exists(path) {
+        load_game(path).map_err(|e| {
+            AppError::Refused(format!("Refusing to save state over a game that failed to load: {e}"))
+        })?
+    } else {
+        Game::default()
+    };
+
+    game.nations.insert(nation, state);
+    save_game(path, &game)
+}
+
+fn save_game(path: &Path, game: &Game) -> Result<()> {
+    let contents = serialize_game(game)?;
+    if let Some(games) = BATCH_GAMES.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+        games.insert(path.to_path_buf(), contents);
+        return Ok(());
+    }
+
+    save_contents(path, contents)
+}
+
+/// Writes an already serialized game to `path`
+fn save_contents(path: &Path, contents: String) -> Result<()> {
+    if is_stdio(path) {
+        return save_stdout_game(contents);
+    }
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if !dir.is_dir() {
+            return Err(AppError::Refused(format!(
+                "Failed to save state: the directory {} does not exist",
+                dir.display()
+            )));
+        }
+    }
+
+    // Only back up games that can be read so the backup can always be recovered from
+    if read_game(path).is_some() {
+        if let Err(e) = fs::copy(path, backup_path(path)) {
+            error!("Failed to back up previous state due to err: {e:?}");
+        }
+    }
+
+    replace_file(path, |file| {
+        write_game(&file, &contents)?;
+        file.sync_all().map_err(AppError::io("save state"))
+    })
+}
+
+/// Replaces the file at `path` with what `write` writes to it
+///
+/// The contents are written to a temporary file first and moved over the old file once `write` succeeds, so a
+/// failed write never leaves a half written game behind.
+fn replace_file(path: &Path, write: impl FnOnce(File) -> Result<()>) -> Result<()> {
+    let temp = temp_path(path);
+    let written = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&temp)
+        .map_err(AppError::io("save state"))
+        .and_then(write)
+        .and_then(|()| fs::rename(&temp, path).map_err(AppError::io("save state")));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    written
+}
+
+/// How saved games are laid out, set by --compact and the config file
+#[derive(Clone, Copy)]
+struct SaveStyle {
+    compact: bool,
+    indent: usize,
+    struct_names: bool,
+}
+
+impl Default for SaveStyle {
+    fn default() -> Self {
+        Self {
+            compact: false,
+            indent: DEFAULT_INDENT,
+            struct_names: false,
+        }
+    }
+}
+
+impl SaveStyle {
+    fn pretty_config(&self) -> PrettyConfig {
+        let config = PrettyConfig::new()
+            .indentor(" ".repeat(self.indent))
+            .struct_names(self.struct_names);
+
+        if self.compact {
+            config.depth_limit(COMPACT_DEPTH).compact_arrays(true)
+        } else {
+            config
+        }
+    }
+}
+
+fn serialize_game(game: &Game) -> Result<String> {
+    serialize_game_as(game, SAVE_STYLE.get().copied().unwrap_or_default())
+}
+
+fn serialize_game_as(game: &Game, style: SaveStyle) -> Result<String> {
+    ron::ser::to_string_pretty(game, style.pretty_config()).map_err(AppError::format("save state"))
+}
+
+/// Writes the game to stdout, and keeps it for the commands that follow
+fn save_stdout_game(contents: String) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{contents}")
+        .and_then(|_| stdout.flush())
+        .map_err(AppError::io("write state to stdout"))?;
+
+    *STDIO_GAME.lock().unwrap_or_else(PoisonError::into_inner) = Some(contents);
+    Ok(())
+}
+
+fn write_game(mut writer: impl Write, contents: &str) -> Result<()> {
+    writer
+        .write_all(contents.as_bytes())
+        .and_then(|()| writer.flush())
+        .map_err(AppError::io("save state"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    /// Tests that read or change environment variables, the config or the active game take turns
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Holds the environment for the test, with the config directory pointed at the scratch directory
+    pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+        let guard = ENV.lock().unwrap_or_else(PoisonError::into_inner);
+        std::env::set_var("XDG_CONFIG_HOME", scratch_dir().join("config"));
+        guard
+    }
+
+    /// A directory of this test run that the files of the tests are kept in
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("axsis-tests-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create scratch directory");
+        dir
+    }
+
+    /// A game file only used by one test, deleted along with its backup when the test is done
+    struct TempGame {
+        path: PathBuf,
+    }
+
+    impl TempGame {
+        fn new(name: &str) -> Self {
+            let game = Self {
+                path: scratch_dir().join(format!("{name}.ron")),
+            };
+            game.remove();
+            game
+        }
+
+        /// A game in which Germany is in `state`
+        fn with_state(name: &str, state: GameState) -> Self {
+            let game = Self::new(name);
+            save(&game.path, Nation::Germany, state).expect("save test game");
+            game
+        }
+
+        fn arg(&self) -> &str {
+            self.path.to_str().expect("scratch paths are UTF-8")
+        }
+
+        fn contents(&self) -> Vec<u8> {
+            fs::read(&self.path).unwrap_or_default()
+        }
+
+        fn state(&self) -> GameState {
+            load(&self.path, Nation::Germany).expect("load test game")
+        }
+
+        /// Every nation in the game
+        fn game(&self) -> Game {
+            load_game(&self.path).expect("load test game")
+        }
+
+        fn remove(&self) {
+            for path in [self.path.clone(), backup_path(&self.path), temp_path(&self.path)] {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Drop for TempGame {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    fn parse_args(args: &[&str]) -> std::result::Result<AppArgs, clap::Error> {
+        AppArgs::try_parse_from(std::iter::once("axsis_and_allies_tracker").chain(args.iter().copied()))
+    }
+
+    /// Runs a command line the way main does, without setting up the logger and the settings of the config
+    fn run_args(args: &[&str]) -> Result<()> {
+        let _env = lock_env();
+        let cli = parse_args(args).expect("valid arguments");
+        run(&cli.options, cli.command)
+    }
+
+    #[test]
+    fn state_file_comes_from_the_flag_then_the_env_var_then_the_default() {
+        let _env = lock_env();
+        let from_env = TempGame::new("env-var");
+        let from_flag = TempGame::new("env-var-flag");
+        std::env::set_var("AXIS_STATE_FILE", from_env.arg());
+
+        let cli = parse_args(&["setup", "20"]).unwrap();
+        assert_eq!(state_file(cli.options.file.as_deref()), from_env.path);
+        run(&cli.options, cli.command).unwrap();
+        assert!(from_env.path.exists());
+
+        let cli = parse_args(&["--file", from_flag.arg(), "setup", "30"]).unwrap();
+        run(&cli.options, cli.command).unwrap();
+        assert_eq!(from_flag.state().ipc, 30);
+        assert_eq!(from_env.state().ipc, 20);
+
+        std::env::remove_var("AXIS_STATE_FILE");
+        let cli = parse_args(&["status"]).unwrap();
+        assert_eq!(state_file(cli.options.file.as_deref()), PathBuf::from(FILE));
+    }
+
+    #[test]
+    fn strict_purchases_can_spend_every_ipc_but_not_one_more() {
+        let exact = TempGame::with_state("spend-exactly", GameState::new(6, Edition::AA1942));
+        let state = add_purchase(&exact.path, Nation::Germany, &[(Troops::Infantry, 2)], None, true).unwrap();
+        assert_eq!(state.get_remaining_ipc(), Some(0));
+
+        let over = TempGame::with_state("spend-one-more", GameState::new(5, Edition::AA1942));
+        let refused = add_purchase(&over.path, Nation::Germany, &[(Troops::Infantry, 2)], None, true);
+        assert!(matches!(refused, Err(AppError::Refused(_))));
+
+        // Without --strict going over is only warned about
+        let state = add_purchase(&over.path, Nation::Germany, &[(Troops::Infantry, 2)], None, false).unwrap();
+        assert_eq!(state.get_remaining_ipc(), Some(-1));
+    }
+
+    #[test]
+    fn setup_leaves_a_game_in_progress_alone_without_force() {
+        let game = TempGame::new("setup-force");
+        run_args(&["--file", game.arg(), "setup", "20"]).unwrap();
+        let before = game.contents();
+
+        assert!(matches!(run_args(&["--file", game.arg(), "setup", "30"]), Err(AppError::Refused(_))));
+        assert_eq!(game.contents(), before);
+
+        run_args(&["--file", game.arg(), "setup", "30", "--force"]).unwrap();
+        assert_eq!(game.state().ipc, 30);
+    }
+
+    #[test]
+    fn the_same_seed_rolls_the_same_hits() {
+        let hits = |seed| {
+            let mut rng = seeded_rng(Some(seed));
+            (0..30).map(|_| dice::roll(&mut rng)).filter(|roll| *roll <= 3).count()
+        };
+
+        assert_eq!(hits(7), hits(7));
+        assert_eq!(hits(42), hits(42));
+    }
+
+    #[test]
+    fn dice_only_hit_on_one_to_six() {
+        assert!(parse_args(&["roll", "5", "--hit-on", "1"]).is_ok());
+        assert!(parse_args(&["roll", "5", "--hit-on", "6"]).is_ok());
+        assert!(parse_args(&["roll", "5", "--hit-on", "0"]).is_err());
+        assert!(parse_args(&["roll", "5", "--hit-on", "7"]).is_err());
+    }
+
+    #[test]
+    fn games_saved_with_infantery_load_as_infantry() {
+        let game = parse_game("(version: 2, nations: {Germany: (ipc: 10, purchases: {Infantery: 3})})").unwrap();
+        assert_eq!(game.nations[&Nation::Germany].purchases.get(&Troops::Infantry), Some(&3));
+
+        // Games from before nations were tracked used the misspelling too
+        let game = parse_game("(ipc: 10, purchases: {Infantery: 2, Tank: 1})").unwrap();
+        assert_eq!(game.nations[&Nation::Germany].purchases.get(&Troops::Infantry), Some(&2));
+
+        assert_eq!(resolve_troop("Infantery"), Ok(Troops::Infantry));
+    }
+
+    #[test]
+    fn absurd_purchases_overflow_cleanly() {
+        let game = TempGame::with_state("overflow", GameState::new(30, Edition::AA1942));
+
+        let result = add_purchase(&game.path, Nation::Germany, &[(Troops::Battleship, i32::MAX)], None, false);
+        assert!(matches!(result, Err(AppError::Overflow(_))));
+
+        let mut state = GameState::new(30, Edition::AA1942);
+        state.purchases.insert(Troops::Battleship, i32::MAX / 2);
+        assert_eq!(state.get_total_cost(), None);
+        assert_eq!(state.get_remaining_ipc(), None);
+    }
+
+    #[test]
+    fn purchases_of_no_or_fewer_troops_are_refused() {
+        let game = TempGame::with_state("purchase-non-positive", GameState::new(30, Edition::AA1942));
+        let before = game.contents();
+
+        for ammount in ["0", "-1"] {
+            let result = run_args(&["--file", game.arg(), "purchase", "tank", ammount]);
+            assert!(matches!(result, Err(AppError::Refused(_))), "purchase of {ammount} tanks");
+        }
+
+        assert_eq!(game.contents(), before);
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        AppArgs::command().debug_assert();
+    }
+
+    #[test]
+    fn options_can_follow_the_command() {
+        let args = parse_args(&["setup", "20", "--nation", "japan"]).unwrap();
+        assert_eq!(args.options.nation, Nation::Japan);
+        let args = parse_args(&["status", "--compact"]).unwrap();
+        assert!(matches!(args.command, Commands::Status { compact: true, .. }));
+        assert!(!args.options.compact);
+    }
+
+    #[test]
+    fn caps_allow_exactly_their_limit() {
+        let game = TempGame::with_state("caps", GameState::new(50, Edition::AA1942));
+        let cap = Some((Troops::Tank, 2));
+        let state = add_purchase(&game.path, Nation::Germany, &[(Troops::Tank, 2)], cap, true).unwrap();
+        assert_eq!(state.caps.get(&Troops::Tank), Some(&2));
+        save(&game.path, Nation::Germany, state).unwrap();
+
+        let refused = add_purchase(&game.path, Nation::Germany, &[(Troops::Tank, 1)], None, true);
+        assert!(matches!(refused, Err(AppError::Refused(_))));
+
+        // Without --strict going over the cap is only warned about
+        let state = add_purchase(&game.path, Nation::Germany, &[(Troops::Tank, 1)], None, false).unwrap();
+        assert_eq!(state.purchases.get(&Troops::Tank), Some(&3));
+    }
+
+    #[test]
+    fn remove_takes_one_troop_unless_told_otherwise() {
+        let mut state = GameState::new(50, Edition::AA1942);
+        state.purchases.insert(Troops::Infantry, 4);
+        let game = TempGame::with_state("remove", state);
+
+        let state = remove_purchase(&game.path, Nation::Germany, Troops::Infantry, Some(1)).unwrap();
+        assert_eq!(state.purchases.get(&Troops::Infantry), Some(&3));
+        save(&game.path, Nation::Germany, state).unwrap();
+
+        // Removing the last of a troop drops it from the purchase
+        let state = remove_purchase(&game.path, Nation::Germany, Troops::Infantry, Some(3)).unwrap();
+        assert_eq!(state.purchases.get(&Troops::Infantry), None);
+    }
+
+    #[test]
+    fn remove_all_takes_every_troop_of_the_type() {
+        let mut state = GameState::new(50, Edition::AA1942);
+        state.purchases.insert(Troops::Infantry, 3);
+        state.purchases.insert(Troops::Tank, 1);
+        let game = TempGame::with_state("remove-all", state);
+        assert_eq!(game.state().get_remaining_ipc(), Some(50 - 9 - 6));
+
+        run_args(&["--file", game.arg(), "remove", "infantry", "--all"]).unwrap();
+        let state = game.state();
+        assert_eq!(state.purchases.get(&Troops::Infantry), None);
+        assert_eq!(state.purchases.get(&Troops::Tank), Some(&1));
+        assert_eq!(state.get_remaining_ipc(), Some(50 - 6));
+    }
+
+    /// A writer whose writes always fail, as if the disk was full
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_writes_leave_the_game_alone() {
+        let game = TempGame::with_state("failed-write", GameState::new(30, Edition::AA1942));
+        let before = game.contents();
+
+        let contents = serialize_game(&Game::default()).unwrap();
+        let result = replace_file(&game.path, |_| write_game(FailingWriter, &contents));
+        assert!(matches!(result, Err(AppError::Io { .. })));
+        assert_eq!(game.contents(), before);
+        assert!(!temp_path(&game.path).exists());
+    }
+
+    #[test]
+    fn unversioned_games_migrate_to_the_current_format() {
+        let round = "(round: 1, purchases: {Tank: 2}, cost: 12, ipc: 18)";
+        let contents = format!("(nations: {{Japan: (ipc: 20, purchases: {{}}, history: [{round}, {round}])}})");
+        let game = parse_game(&contents).unwrap().migrate().unwrap();
+        assert_eq!(game.version, VERSION);
+        assert_eq!(game.nations[&Nation::Japan].round, 3);
+
+        let future = parse_game(&format!("(version: {}, nations: {{}})", VERSION + 1)).unwrap();
+        assert!(future.migrate().is_err());
+    }
+
+    #[test]
+    fn failed_batches_save_nothing() {
+        let game = TempGame::with_state("batch", GameState::new(30, Edition::AA1942));
+        let before = game.contents();
+        let batch = scratch_dir().join("batch.txt");
+
+        fs::write(&batch, "purchase tank 1\npurchase tank 0\n").unwrap();
+        assert!(run_args(&["--file", game.arg(), "batch", batch.to_str().unwrap()]).is_err());
+        assert_eq!(game.contents(), before);
+
+        // A finished batch is saved once, so a single undo reverts all of it
+        fs::write(&batch, "purchase tank 1\npurchase infantry 2\n").unwrap();
+        run_args(&["--file", game.arg(), "batch", batch.to_str().unwrap()]).unwrap();
+        assert_eq!(game.state().get_total_cost(), Some(12));
+        run_args(&["--file", game.arg(), "undo"]).unwrap();
+        assert_eq!(game.contents(), before);
+        let _ = fs::remove_file(batch);
+    }
+
+    #[test]
+    fn the_ipc_spent_adds_up_over_the_game() {
+        let game = TempGame::with_state("total-spent", GameState::new(30, Edition::AA1942));
+        for purchase in [["tank", "1"], ["infantry", "2"], ["fighter", "1"]] {
+            run_args(&["--file", game.arg(), "purchase", purchase[0], purchase[1]]).unwrap();
+            run_args(&["--file", game.arg(), "commit", "10", "--yes"]).unwrap();
+        }
+
+        let state = game.state();
+        assert_eq!(state.total_spent, 6 + 6 + 10);
+        assert_eq!(state.history.len(), 3);
+    }
+
+    #[test]
+    fn dry_runs_leave_the_game_alone() {
+        let game = TempGame::with_state("dry-run", GameState::new(30, Edition::AA1942));
+        run_args(&["--file", game.arg(), "purchase", "tank", "2"]).unwrap();
+        let before = game.contents();
+
+        run_args(&["--file", game.arg(), "purchase", "tank", "1", "--dry-run"]).unwrap();
+        run_args(&["--file", game.arg(), "remove", "tank", "--dry-run"]).unwrap();
+        assert_eq!(game.contents(), before);
+    }
+
+    #[test]
+    fn failed_commands_exit_with_a_failure() {
+        let game = TempGame::with_state("exit-code", GameState::new(5, Edition::AA1942));
+        run_args(&["--file", game.arg(), "purchase", "tank", "1"]).unwrap();
+
+        let result = run_args(&["--file", game.arg(), "commit", "10", "--yes"]);
+        assert!(matches!(result, Err(AppError::Refused(_))));
+        assert_eq!(exit_code(result), ExitCode::FAILURE);
+        assert_eq!(exit_code(run_args(&["--file", game.arg(), "status"])), ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn status_renders_as_markdown() {
+        let mut state = GameState::new(30, Edition::AA1942);
+        state.purchases.insert(Troops::Tank, 1);
+        state.purchases.insert(Troops::Infantry, 2);
+
+        let expected = "\
+| Troop | Amount | Cost | Subtotal | A/D/M |
+| :--- | ---: | ---: | ---: | ---: |
+| Infantry | 2 | 3 | 6 | 1/2/1 |
+| Tank | 1 | 6 | 6 | 3/3/2 |
+
+- Round: 1
+- Edition: 1942 Second Edition
+- Land: 3 units (12 IPC)
+- Air: 0 units (0 IPC)
+- Sea: 0 units (0 IPC)
+- Total cost: 12 IPC
+- Remaining IPC: 18
+";
+        assert_eq!(state.render_markdown(PurchaseOrder::default()), expected);
+    }
+
+    #[test]
+    fn reset_forgets_the_actions_of_the_round() {
+        let game = TempGame::with_state("reset", GameState::new(30, Edition::AA1942));
+        run_args(&["--file", game.arg(), "purchase", "tank", "2"]).unwrap();
+
+        let state = reset_purchases(&game.path, Nation::Germany, true).unwrap();
+        assert!(state.purchases.is_empty());
+        assert!(state.actions.is_empty());
+    }
+
+    #[test]
+    fn stdio_can_be_picked_after_the_command() {
+        for args in [["status", "--file", "-"], ["--file", "-", "status"]] {
+            let args = parse_args(&args).unwrap();
+            assert!(args.options.file.as_deref().is_some_and(is_stdio));
+        }
+    }
+
+    #[test]
+    fn converting_reprices_the_purchases() {
+        let mut state = GameState::new(30, Edition::AA1942);
+        state.purchases.insert(Troops::Tank, 2);
+        state.purchases.insert(Troops::Battleship, 1);
+        assert_eq!(state.get_total_cost(), Some(32));
+        let game = TempGame::with_state("convert", state);
+
+        let state = convert(&game.path, Nation::Germany, Edition::Revised, true, false).unwrap();
+        assert_eq!(state.edition, Edition::Revised);
+        assert_eq!(state.get_total_cost(), Some(34));
+
+        // Going over the ipc is only allowed without --strict
+        let refused = convert(&game.path, Nation::Germany, Edition::Revised, true, true);
+        assert!(matches!(refused, Err(AppError::Refused(_))));
+    }
+
+    #[test]
+    fn setup_refuses_negative_ipc() {
+        let game = TempGame::new("setup-negative");
+        let result = run_args(&["--file", game.arg(), "setup", "-5"]);
+        assert!(matches!(result, Err(AppError::Refused(_))));
+        assert!(!game.path.exists());
+
+        run_args(&["--file", game.arg(), "setup", "0"]).unwrap();
+        assert_eq!(game.state().ipc, 0);
+    }
+
+    #[test]
+    fn commit_keeps_to_the_category_caps() {
+        let game = TempGame::with_state("category-caps", GameState::new(50, Edition::AA1942));
+        run_args(&["--file", game.arg(), "caps", "--max-air", "2"]).unwrap();
+        run_args(&["--file", game.arg(), "purchase", "fighter", "2"]).unwrap();
+        run_args(&["--file", game.arg(), "commit", "10", "--yes"]).unwrap();
+
+        run_args(&["--file", game.arg(), "purchase", "fighter", "3"]).unwrap();
+        let before = game.contents();
+        let result = run_args(&["--file", game.arg(), "commit", "10", "--yes"]);
+        assert!(matches!(result, Err(AppError::Refused(_))));
+        assert_eq!(game.contents(), before);
+    }
+
+    #[test]
+    fn commit_changes_the_usual_income_by_the_delta() {
+        let game = TempGame::with_state("income-delta", GameState::new(0, Edition::AA1942));
+        run_args(&["--file", game.arg(), "set-income", "30"]).unwrap();
+        run_args(&["--file", game.arg(), "commit", "--delta", "-5", "--skip-build", "--yes"]).unwrap();
+
+        let state = game.state();
+        assert_eq!(state.base_income, Some(25));
+        assert_eq!(state.ipc, 25);
+    }
+
+    #[test]
+    fn troops_have_german_names() {
+        assert_eq!(Troops::Tank.name_in(Lang::De), "Panzer");
+        assert_eq!(Troops::Submarine.name_in(Lang::De), "U-Boot");
+        assert_eq!(Troops::Tank.name_in(Lang::En), "Tank");
+        assert!(Troops::value_variants().iter().all(|troop| !troop.name_in(Lang::De).is_empty()));
+        assert_eq!(parse_args(&["--lang", "de", "status"]).unwrap().options.lang, Some(Lang::De));
+    }
+
+    #[test]
+    fn saves_do_not_depend_on_the_purchase_order() {
+        let game = |order: &[(Troops, i32)]| {
+            let mut state = GameState::new(30, Edition::AA1942);
+            for (troop, ammount) in order {
+                state.purchases.insert(*troop, *ammount);
+                state.caps.insert(*troop, *ammount);
+            }
+            Game {
+                version: VERSION,
+                nations: BTreeMap::from([(Nation::Germany, state)]),
+            }
+        };
+
+        let forwards = game(&[(Troops::Tank, 1), (Troops::Infantry, 2), (Troops::Fighter, 3), (Troops::Cruiser, 4)]);
+        let backwards = game(&[(Troops::Cruiser, 4), (Troops::Fighter, 3), (Troops::Infantry, 2), (Troops::Tank, 1)]);
+        assert_eq!(serialize_game(&forwards).unwrap(), serialize_game(&backwards).unwrap());
+    }
+
+    #[test]
+    fn buy_max_buys_what_the_ipc_and_caps_allow() {
+        let bought = |ipc, cap: Option<i32>| {
+            let mut state = GameState::new(ipc, Edition::AA1942);
+            if let Some(cap) = cap {
+                state.caps.insert(Troops::Tank, cap);
+            }
+            let game = TempGame::with_state("buy-max", state);
+            buy_max(&game.path, Nation::Germany, Troops::Tank, false).map(|state| state.purchases[&Troops::Tank])
+        };
+
+        assert_eq!(bought(18, None).unwrap(), 3);
+        assert_eq!(bought(23, None).unwrap(), 3);
+        assert_eq!(bought(30, Some(2)).unwrap(), 2);
+        assert!(matches!(bought(5, None), Err(AppError::Refused(_))));
+        assert!(matches!(bought(30, Some(0)), Err(AppError::Refused(_))));
+    }
+
+    #[test]
+    fn only_a_yes_confirms() {
+        assert!(confirm("Commit?", &mut "y\n".as_bytes(), true));
+        assert!(confirm("Commit?", &mut "Yes\n".as_bytes(), true));
+        assert!(!confirm("Commit?", &mut "n\n".as_bytes(), true));
+        assert!(!confirm("Commit?", &mut "".as_bytes(), true));
+
+        // Scripts are never asked, so they don't hang waiting for an answer
+        assert!(!confirm("Commit?", &mut "y\n".as_bytes(), false));
+    }
+
+    #[test]
+    fn missing_games_point_to_setup() {
+        let game = TempGame::new("missing");
+        let result = run_args(&["--file", game.arg(), "status"]);
+        assert!(matches!(&result, Err(AppError::FileNotFound(path)) if *path == game.path));
+        assert!(result.unwrap_err().to_string().contains("run `setup <ipc>` first"));
+    }
+
+    #[test]
+    fn troops_resolve_from_the_start_of_their_name() {
+        assert_eq!(resolve_troop("art"), Ok(Troops::Artillery));
+        assert_eq!(resolve_troop("Battle"), Ok(Troops::Battleship));
+        assert!(resolve_troop("ta").unwrap_err().contains("could be any of"));
+        assert!(resolve_troop("a").unwrap_err().contains("could be any of"));
+        assert!(resolve_troop("zeppelin").unwrap_err().contains("unknown troop"));
+
+        // An exact alias wins over the longer names it is the start of
+        assert_eq!(resolve_troop("t"), Ok(Troops::Tank));
+    }
+
+    #[test]
+    fn afford_counts_what_the_remaining_ipc_buys() {
+        let mut state = GameState::new(25, Edition::AA1942);
+        state.purchases.insert(Troops::Infantry, 1);
+
+        let affordable = state.affordable(None).unwrap();
+        assert_eq!(affordable[&Troops::Tank], 3);
+        assert_eq!(affordable[&Troops::Battleship], 1);
+        assert_eq!(affordable[&Troops::Fighter], 2);
+
+        let air = state.affordable(Some(Category::Air)).unwrap();
+        assert!(air.keys().all(|troop| troop.category() == Category::Air));
+        assert_eq!(air.get(&Troops::Tank), None);
+
+        // Going over the ipc affords nothing rather than a negative amount
+        state.purchases.insert(Troops::Battleship, 2);
+        assert!(state.affordable(None).unwrap().values().all(|ammount| *ammount == 0));
+    }
+
+    #[test]
+    fn no_persist_changes_no_files() {
+        let game = TempGame::with_state("no-persist", GameState::new(30, Edition::AA1942));
+        run_args(&["--file", game.arg(), "purchase", "tank", "1"]).unwrap();
+        let before = game.contents();
+
+        run_args(&["--file", game.arg(), "--no-persist", "purchase", "tank", "2"]).unwrap();
+        assert_eq!(game.contents(), before);
+
+        let commands: [&[&str]; 6] = [
+            &["import", game.arg()],
+            &["undo"],
+            &["plan", "save", "no-persist"],
+            &["game", "new", "no-persist"],
+            &["game", "delete", "no-persist", "--yes"],
+            &["config", "set", "indent", "2"],
+        ];
+        for command in commands {
+            let args = [&["--file", game.arg(), "--no-persist"], command].concat();
+            assert!(matches!(run_args(&args), Err(AppError::Refused(_))), "{command:?}");
+        }
+        assert_eq!(game.contents(), before);
+    }
+
+
+    #[test]
+    fn compact_saves_load_back_the_same() {
+        let mut state = GameState::new(30, Edition::AA1942);
+        state.purchases.insert(Troops::Tank, 1);
+        state.purchases.insert(Troops::Infantry, 2);
+        let game = TempGame::with_state("compact", state);
+        let saved = game.game();
+
+        let style = SaveStyle {
+            compact: true,
+            ..SaveStyle::default()
+        };
+        let contents = serialize_game_as(&saved, style).unwrap();
+        assert!(contents.lines().any(|line| line.trim() == "purchases: {Infantry: 2, Tank: 1},"), "{contents}");
+        assert!(contents.lines().count() < serialize_game(&saved).unwrap().lines().count());
+
+        save_contents(&game.path, contents).unwrap();
+        assert_eq!(game.game(), saved);
+        assert_eq!(parse_game(&String::from_utf8(game.contents()).unwrap()).unwrap(), saved);
+    }
+
+
+    #[test]
+    fn games_survive_a_round_trip_through_json() {
+        let game = TempGame::new("export");
+        run_args(&["--file", game.arg(), "setup", "40"]).unwrap();
+        run_args(&["--file", game.arg(), "--nation", "japan", "setup", "30", "--edition", "revised"]).unwrap();
+        run_args(&["--file", game.arg(), "purchase", "tank", "2"]).unwrap();
+        run_args(&["--file", game.arg(), "commit", "12", "--bonus", "5", "--yes"]).unwrap();
+        run_args(&["--file", game.arg(), "--nation", "japan", "purchase", "battleship", "1"]).unwrap();
+
+        let json = TempGame::new("export-json");
+        run_args(&["--file", game.arg(), "export", "--format", "json", "--out", json.arg()]).unwrap();
+        let imported = TempGame::new("import");
+        run_args(&["--file", imported.arg(), "import", json.arg(), "--format", "json"]).unwrap();
+
+        let exported = game.game();
+        assert_eq!(exported.nations.len(), 2);
+        assert_eq!(imported.game(), exported);
+    }
+
+
+    #[test]
+    fn history_copes_with_a_round_of_zero() {
+        let round = "(round: 1, purchases: {Tank: 2}, cost: 12, ipc: 18)";
+        let contents = format!("(version: 2, nations: {{Germany: (ipc: 18, purchases: {{}}, round: 0, history: [{round}])}})");
+        let state = parse_game(&contents).unwrap().nations.remove(&Nation::Germany).unwrap();
+        assert_eq!(state.round, 0);
+        let game = TempGame::with_state("history-round-zero", state);
+
+        run_args(&["--file", game.arg(), "history", "--since", "5"]).unwrap();
+    }
+
+
+    #[test]
+    fn suggestions_keep_to_the_caps() {
+        let troops = [Troops::Infantry, Troops::Artillery, Troops::Tank];
+        let mut state = GameState::new(30, Edition::AA1942);
+        assert_eq!(fill_budget(&troops, &state, 30), [(Troops::Tank, 5)]);
+
+        state.caps.insert(Troops::Tank, 1);
+        state.category_caps.insert(Category::Land, 4);
+        let suggestion = fill_budget(&troops, &state, 30);
+        assert_eq!(suggestion, [(Troops::Tank, 1), (Troops::Artillery, 3)]);
+
+        // Everything suggested can be bought and committed as it is
+        state.purchases.extend(suggestion);
+        assert!(state.category_cap_violations().is_empty());
+        let game = TempGame::with_state("optimize-caps", GameState::new(30, Edition::AA1942));
+        let capped = Some((Troops::Tank, 1));
+        assert!(add_purchase(&game.path, Nation::Germany, &[(Troops::Tank, 1), (Troops::Artillery, 3)], capped, true).is_ok());
+    }
+}
@@ -0,0 +1,65 @@
+//! Purchases saved under a name so they can be reused in later games
+//!
+//! Plans are stored one per file in the `plans` folder of the config directory.
+use ron::{de::from_reader, ser::{PrettyConfig, to_writer_pretty}};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    path::PathBuf,
+};
+
+use crate::{
+    config::{list_named_files, named_file},
+    error::{AppError, Result},
+    Troops,
+};
+
+/// The folder of the config directory plans are stored in
+const PLANS: &str = "plans";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Plan {
+    pub purchases: BTreeMap<Troops, i32>,
+}
+
+impl Plan {
+    /// Loads the plan with the given name, if it has been saved
+    pub fn load(name: &str) -> Result<Self> {
+        let path = plan_path(name)?;
+
+        match OpenOptions::new().read(true).open(&path) {
+            Ok(file) => from_reader(file).map_err(AppError::format(format!("read plan from {}", path.display()))),
+            Err(_) => Err(AppError::Refused(format!(
+                "There is no plan called {name}, use plan list to see the saved plans"
+            ))),
+        }
+    }
+
+    /// Saves the plan under the given name, replacing any plan already saved under it
+    pub fn save(&self, name: &str) -> Result<()> {
+        let path = plan_path(name)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(AppError::io(format!("create plan directory {}", dir.display())))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(AppError::io("save plan"))?;
+
+        to_writer_pretty(file, self, PrettyConfig::default()).map_err(AppError::format("save plan"))
+    }
+}
+
+/// The names of every saved plan, in alphabetical order
+pub fn list() -> Vec<String> {
+    list_named_files(PLANS)
+}
+
+fn plan_path(name: &str) -> Result<PathBuf> {
+    named_file(PLANS, name)
+}
@@ -0,0 +1,44 @@
+//! Named games kept in the config directory, so several can be played without passing --file
+//!
+//! The name of the game commands apply to is stored in the `active_game` file next to them.
+use std::{fs, path::PathBuf};
+
+use crate::{
+    config::{config_dir, list_named_files, named_file, no_config_dir},
+    error::{AppError, Result},
+};
+
+/// The folder of the config directory games are stored in
+const GAMES: &str = "games";
+
+/// The file a named game is stored in
+pub fn path(name: &str) -> Result<PathBuf> {
+    named_file(GAMES, name)
+}
+
+/// The names of every game, in alphabetical order
+pub fn list() -> Vec<String> {
+    list_named_files(GAMES)
+}
+
+/// The name of the game commands apply to, if one was switched to
+pub fn active() -> Option<String> {
+    let pointer = config_dir()?.join("active_game");
+    let name = fs::read_to_string(pointer).ok()?;
+    let name = name.trim();
+
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Makes `name` the game commands apply to, or goes back to using --file if it is `None`
+pub fn set_active(name: Option<&str>) -> Result<()> {
+    let dir = config_dir().ok_or_else(no_config_dir)?;
+    fs::create_dir_all(&dir).map_err(AppError::io(format!("create config directory {}", dir.display())))?;
+
+    let pointer = dir.join("active_game");
+    match name {
+        Some(name) => fs::write(&pointer, name),
+        None => fs::remove_file(&pointer),
+    }
+    .map_err(AppError::io(format!("update {}", pointer.display())))
+}
@@ -0,0 +1,146 @@
+//! Plain text tables with aligned columns
+use owo_colors::Style;
+use std::fmt::Write;
+
+use crate::paint;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+struct Column {
+    header: &'static str,
+    align: Align,
+    style: Style,
+}
+
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self {
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn column(self, header: &'static str, align: Align) -> Self {
+        self.styled_column(header, align, Style::new())
+    }
+
+    /// Adds a column whose cells are painted with `style` when rendering with colors
+    pub fn styled_column(mut self, header: &'static str, align: Align, style: Style) -> Self {
+        self.columns.push(Column { header, align, style });
+        self
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.chars().count())
+                    .fold(column.header.chars().count(), usize::max)
+            })
+            .collect()
+    }
+
+    /// Renders the table as `write` does
+    pub fn render(&self, color: bool) -> String {
+        let mut output = String::new();
+        // Writing to a String can't fail
+        let _ = self.write(&mut output, color);
+        output
+    }
+
+    /// Writes the table with a header and a separator line, each line indented by a tab
+    pub fn write(&self, f: &mut dyn Write, color: bool) -> std::fmt::Result {
+        let widths = self.widths();
+
+        let header: Vec<String> = self.columns.iter().map(|column| column.header.to_string()).collect();
+        self.write_row(f, &header, &widths, None)?;
+
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        self.write_row(f, &separator, &widths, None)?;
+
+        for row in self.rows.iter() {
+            self.write_row(f, row, &widths, Some(color))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the table as a GitHub flavored markdown table
+    pub fn write_markdown(&self, f: &mut dyn Write) -> std::fmt::Result {
+        let header: Vec<String> = self.columns.iter().map(|column| markdown_cell(column.header)).collect();
+        writeln!(f, "| {} |", header.join(" | "))?;
+
+        let alignments: Vec<&str> = self
+            .columns
+            .iter()
+            .map(|column| match column.align {
+                Align::Left => ":---",
+                Align::Right => "---:",
+            })
+            .collect();
+        writeln!(f, "| {} |", alignments.join(" | "))?;
+
+        for row in self.rows.iter() {
+            let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+            writeln!(f, "| {} |", cells.join(" | "))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_row(&self, f: &mut dyn Write, row: &[String], widths: &[usize], color: Option<bool>) -> std::fmt::Result {
+        let mut cells = Vec::with_capacity(self.columns.len());
+
+        for ((column, width), cell) in self.columns.iter().zip(widths).zip(row) {
+            let padded = match column.align {
+                Align::Left => format!("{cell:<width$}"),
+                Align::Right => format!("{cell:>width$}"),
+            };
+
+            // Pad before painting so the escape codes don't count towards the width
+            cells.push(match color {
+                Some(color) => paint(padded, column.style, color),
+                None => padded,
+            });
+        }
+
+        writeln!(f, "\t{}", cells.join("  ").trim_end())
+    }
+}
+
+/// Escapes the pipes that would otherwise end the cell
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipes_are_escaped_in_markdown() {
+        let mut table = Table::new().column("Reason|Note", Align::Left).column("IPC", Align::Right);
+        table.add_row(vec!["tech | rockets".to_string(), "5".to_string()]);
+
+        let mut output = String::new();
+        table.write_markdown(&mut output).unwrap();
+        assert_eq!(output, "| Reason\\|Note | IPC |\n| :--- | ---: |\n| tech \\| rockets | 5 |\n");
+    }
+}
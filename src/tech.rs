@@ -0,0 +1,217 @@
+//! Research of new technologies
+//!
+//! Research is bought as tokens, and every token rolled as a 6 is a breakthrough.
+//! Tokens are kept between turns until a breakthrough is made.
+//! Which technologies can be researched, and what they do, follows the tech chart of the edition.
+use clap::ValueEnum;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::{dice, Troops, IPC};
+
+#[derive(ValueEnum, Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Tech {
+    AdvancedArtillery,
+    Rockets,
+    Paratroopers,
+    IncreasedFactoryProduction,
+    WarBonds,
+    ImprovedMechanizedInfantry,
+    SuperSubmarines,
+    JetFighters,
+    ImprovedShipyards,
+    Radar,
+    LongRangeAircraft,
+    HeavyBombers,
+}
+
+impl Display for Tech {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Tech::AdvancedArtillery => "Advanced Artillery",
+            Tech::Rockets => "Rockets",
+            Tech::Paratroopers => "Paratroopers",
+            Tech::IncreasedFactoryProduction => "Increased Factory Production",
+            Tech::WarBonds => "War Bonds",
+            Tech::ImprovedMechanizedInfantry => "Improved Mechanized Infantry",
+            Tech::SuperSubmarines => "Super Submarines",
+            Tech::JetFighters => "Jet Fighters",
+            Tech::ImprovedShipyards => "Improved Shipyards",
+            Tech::Radar => "Radar",
+            Tech::LongRangeAircraft => "Long Range Aircraft",
+            Tech::HeavyBombers => "Heavy Bombers",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// The research chart of an edition, deciding what can be researched and what it does
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TechChart {
+    /// The chart of the 1942 Second Edition, also used for Revised
+    SecondEdition,
+    Global1940,
+}
+
+impl TechChart {
+    /// The price of a single research token
+    pub const fn token_cost(&self) -> IPC {
+        match self {
+            TechChart::SecondEdition | TechChart::Global1940 => 5,
+        }
+    }
+
+    /// The technologies a breakthrough can unlock, by their position on the chart
+    ///
+    /// Each chart has two halves of six rows, and the order decides what each roll unlocks.
+    pub const fn breakthroughs(&self) -> &'static [Tech] {
+        match self {
+            TechChart::SecondEdition => &[
+                Tech::AdvancedArtillery,
+                Tech::Rockets,
+                Tech::Paratroopers,
+                Tech::IncreasedFactoryProduction,
+                Tech::WarBonds,
+                Tech::ImprovedMechanizedInfantry,
+                Tech::SuperSubmarines,
+                Tech::JetFighters,
+                Tech::ImprovedShipyards,
+                Tech::Radar,
+                Tech::LongRangeAircraft,
+                Tech::HeavyBombers,
+            ],
+            // The Land & Production chart, then the Air & Naval chart
+            TechChart::Global1940 => &[
+                Tech::WarBonds,
+                Tech::IncreasedFactoryProduction,
+                Tech::ImprovedMechanizedInfantry,
+                Tech::AdvancedArtillery,
+                Tech::Rockets,
+                Tech::Paratroopers,
+                Tech::JetFighters,
+                Tech::SuperSubmarines,
+                Tech::HeavyBombers,
+                Tech::LongRangeAircraft,
+                Tech::Radar,
+                Tech::ImprovedShipyards,
+            ],
+        }
+    }
+}
+
+impl Display for TechChart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TechChart::SecondEdition => write!(f, "1942 Second Edition"),
+            TechChart::Global1940 => write!(f, "Global 1940"),
+        }
+    }
+}
+
+/// The outcome of rolling the research tokens
+pub struct Research {
+    pub rolls: Vec<u8>,
+    pub breakthroughs: Vec<Tech>,
+}
+
+/// Rolls one die per token, each 6 unlocks the technology at a position rolled on the chart
+///
+/// Positions of technologies that were already researched are rolled again.
+pub fn research(tokens: u32, researched: &[Tech], chart: TechChart, rng: &mut impl Rng) -> Research {
+    let rolls: Vec<u8> = (0..tokens).map(|_| dice::roll(rng)).collect();
+    let hits = rolls.iter().filter(|roll| **roll == 6).count();
+
+    let table = chart.breakthroughs();
+    let mut breakthroughs: Vec<Tech> = Vec::new();
+    for _ in 0..hits {
+        let known = |tech: &Tech, breakthroughs: &[Tech]| researched.contains(tech) || breakthroughs.contains(tech);
+        if table.iter().all(|tech| known(tech, &breakthroughs)) {
+            break;
+        }
+
+        loop {
+            let tech = table[chart_position(rng)];
+            if !known(&tech, &breakthroughs) {
+                breakthroughs.push(tech);
+                break;
+            }
+        }
+    }
+
+    Research { rolls, breakthroughs }
+}
+
+/// Rolls a die for the half of the chart, 1-3 for the first and 4-6 for the second, then one for the row in it
+fn chart_position(rng: &mut impl Rng) -> usize {
+    let half = usize::from(dice::roll(rng) > 3);
+    half * 6 + usize::from(dice::roll(rng)) - 1
+}
+
+/// How a single unit fights once the technologies of its side are taken into account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Combat {
+    pub troop: Troops,
+    pub attack: u8,
+    pub defense: u8,
+    /// The number of dice rolled in attack, of which the best counts
+    pub attack_dice: u8,
+}
+
+/// Every way a technology changes how a troop fights is applied here, as described by the chart
+pub fn apply_tech_effects(troop: Troops, techs: &[Tech], chart: TechChart) -> Combat {
+    let mut combat = Combat {
+        troop,
+        attack: troop.attack(),
+        defense: troop.defense(),
+        attack_dice: 1,
+    };
+
+    if troop == Troops::Bomber && techs.contains(&Tech::HeavyBombers) {
+        combat.attack_dice = 2;
+    }
+
+    if troop == Troops::Submarine && techs.contains(&Tech::SuperSubmarines) {
+        combat.attack += 1;
+    }
+
+    // Jet fighters defend better on the Second Edition chart, but attack better on the Global chart
+    if troop == Troops::Fighter && techs.contains(&Tech::JetFighters) {
+        match chart {
+            TechChart::SecondEdition => combat.defense += 1,
+            TechChart::Global1940 => combat.attack += 1,
+        }
+    }
+
+    combat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn breakthroughs(chart: TechChart, researched: &[Tech]) -> Vec<Tech> {
+        research(12, researched, chart, &mut StdRng::seed_from_u64(3)).breakthroughs
+    }
+
+    #[test]
+    fn breakthroughs_depend_on_the_chart() {
+        let second_edition = breakthroughs(TechChart::SecondEdition, &[]);
+        let global = breakthroughs(TechChart::Global1940, &[]);
+        assert!(!second_edition.is_empty());
+        assert_eq!(second_edition, breakthroughs(TechChart::SecondEdition, &[]));
+        assert_ne!(second_edition, global);
+    }
+
+    #[test]
+    fn researched_technologies_are_not_unlocked_again() {
+        let first = breakthroughs(TechChart::SecondEdition, &[]);
+        let again = breakthroughs(TechChart::SecondEdition, &first);
+        assert!(again.iter().all(|tech| !first.contains(tech)));
+
+        assert!(again.iter().enumerate().all(|(index, tech)| !again[..index].contains(tech)));
+        assert!(breakthroughs(TechChart::Global1940, TechChart::Global1940.breakthroughs()).is_empty());
+    }
+}